@@ -1,6 +1,7 @@
 use cgmath::{Point2 as CPoint};
 use crate::effect::{Effect};
 use crate::render;
+use crate::mesh_gen;
 use crate::unit;
 use crate::unit::*;
 
@@ -10,8 +11,8 @@ use nalgebra as nal;
 use ncollide2d;
 use ncollide2d::shape::{Shape};
 use ncollide2d::query::{Contact};
-use ggez::nalgebra as na;
-use nal::{Point2, Isometry2, Vector2};
+use ncollide2d::bounding_volume::{AABB};
+use nal::{Point2};
 
 
 #[derive(Copy, Clone, PartialEq)]
@@ -23,6 +24,7 @@ pub enum DrawContext{
 pub type ColPolygon = ncollide2d::shape::ConvexPolygon::<f32>;
 pub type ColBall    = ncollide2d::shape::Ball::<f32>;
 pub type Polyline   = ncollide2d::shape::Polyline::<f32>;
+pub type ColAABB    = AABB::<f32>;
 
 pub fn rect_col_polygon(width : f32, height : f32) -> ColPolygon{
 
@@ -45,6 +47,10 @@ pub enum Collision{
     PolyCollision{ ncol : Polyline}
 }
 
+pub const PROJECTILE_SIZE  : f32 = 3.0;
+/// Distance covered by a projectile each second.
+pub const PROJECTILE_SPEED : f32 = 480.0;
+
 impl Collision {
     pub fn get_size(&self) -> unit::Size {
         match self {
@@ -65,6 +71,28 @@ impl Collision {
     }
 }
 
+impl Collision {
+    /// The same shape, `scale` times bigger.
+    pub fn scaled(&self, scale : f32) -> Collision {
+        match self {
+            Collision::NoCollision{..} => self.clone(),
+            Collision::RectCollision{width, height, ..} => Collision::RectCollision{
+                width  : width * scale,
+                height : height * scale,
+                ncol   : rect_col_polygon(width * scale, height * scale)
+            },
+            Collision::DiscCollision{radius, ..} => Collision::DiscCollision{
+                radius : radius * scale,
+                ncol   : ColBall::new(radius * scale)
+            },
+            Collision::PolyCollision{ncol} => {
+                let points = ncol.points().iter().map(|p| p * scale).collect();
+                Collision::PolyCollision{ ncol : Polyline::new(points, None) }
+            }
+        }
+    }
+}
+
 pub fn mk_nocol() -> Collision{
     Collision::NoCollision{ ncol : ColBall::new(0.0001f32)}
 }
@@ -78,10 +106,18 @@ pub fn mk_polycol(pts : &Vec::<unit::Position>) -> Collision{
 }
 
 
-pub fn collides2(pos1 : &unit::Position, col1 : &Collision, pos2 : &unit::Position, col2 : &Collision) -> Option<Contact<f32>>{
-    let prediction = 1.0f32;
-    let iso1 = Isometry2::new(Vector2::new(pos1.x, pos1.y), na::zero());
-    let iso2 = Isometry2::new(Vector2::new(pos2.x, pos2.y), na::zero());    
+/// Distance under which two shapes are considered touching.
+pub const COLLISION_PREDICTION : f32 = 1.0;
+
+pub fn collides2(t1 : &unit::Transform, col1 : &Collision, t2 : &unit::Transform, col2 : &Collision) -> Option<Contact<f32>>{
+    let prediction = COLLISION_PREDICTION;
+    let iso1 = t1.isometry();
+    let iso2 = t2.isometry();
+
+    let scaled1;
+    let col1 = if t1.scale != 1.0 { scaled1 = col1.scaled(t1.scale); &scaled1 } else { col1 };
+    let scaled2;
+    let col2 = if t2.scale != 1.0 { scaled2 = col2.scaled(t2.scale); &scaled2 } else { col2 };
 
     let shp1 = col1.get_ncol();
     let shp2 = col2.get_ncol();
@@ -95,12 +131,12 @@ pub fn collides2(pos1 : &unit::Position, col1 : &Collision, pos2 : &unit::Positi
     )
 }
 
-pub fn collides( pos1 : &unit::Position, col1 : &Collision, pos2 : &unit::Position, col2 : &Collision) -> bool {
-    let v1 = CPoint{x : pos1.x ,y : pos1.y };
-    let v2 = CPoint{x : pos2.x ,y : pos2.y };
+pub fn collides( t1 : &unit::Transform, col1 : &Collision, t2 : &unit::Transform, col2 : &Collision) -> bool {
+    let v1 = CPoint{x : t1.position.x ,y : t1.position.y };
+    let v2 = CPoint{x : t2.position.x ,y : t2.position.y };
     let delta = v2-v1;
 
-    if let Some(_) = collides2(pos1, col1, pos2, col2){
+    if let Some(_) = collides2(t1, col1, t2, col2){
         return  true;
     };
 
@@ -121,11 +157,26 @@ pub fn collides( pos1 : &unit::Position, col1 : &Collision, pos2 : &unit::Positi
 }
 
 
-#[derive(PartialEq)]
+/// Bit set of collision layers.
+/// An actor sits on its `layer` and reacts to the actors whose layer is in its `mask`.
+pub type CollisionLayers = u32;
+
+pub const LAYER_NONE       : CollisionLayers = 0;
+pub const LAYER_PLAYER     : CollisionLayers = 1 << 0;
+pub const LAYER_TERRAIN    : CollisionLayers = 1 << 1;
+pub const LAYER_ENEMY      : CollisionLayers = 1 << 2;
+pub const LAYER_PICKUP     : CollisionLayers = 1 << 3;
+pub const LAYER_PROJECTILE : CollisionLayers = 1 << 4;
+pub const LAYER_TRIGGER    : CollisionLayers = 1 << 5;
+/// What costs a ship its shield or a life.
+pub const LAYER_HAZARDS    : CollisionLayers = LAYER_TERRAIN | LAYER_ENEMY;
+
+#[derive(PartialEq, Copy, Clone)]
 pub enum ActorType {
     Background,
     Foreground,
     Player,
+    Projectile,
     Terrain,
     UI,
     Camera
@@ -135,20 +186,37 @@ impl ActorType{
     pub fn make(self) -> Actor {
         Actor::new(self, unit::get_id())
     }
+
+    /// Layer and mask an actor of this type starts with.
+    fn default_layers(&self) -> (CollisionLayers, CollisionLayers) {
+        match self {
+            ActorType::Player     => (LAYER_PLAYER, LAYER_HAZARDS),
+            ActorType::Background => (LAYER_TERRAIN, LAYER_NONE),
+            ActorType::Foreground => (LAYER_ENEMY, LAYER_PLAYER),
+            ActorType::Projectile => (LAYER_PROJECTILE, LAYER_TERRAIN),
+            _ => (LAYER_NONE, LAYER_NONE)
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct Actor {
     pub atype      : ActorType,
     pub id         : Id,
     //==
-    pub transform  : unit::Position,    
+    pub transform  : unit::Transform,
     //==
     drawable   : Id,
     pub drawctx    : DrawContext,
     pub visible    : bool,
     //==
-    pub collision    : Collision,
+    collision        : Collision,
+    local_aabb       : ColAABB,
+    pub layer        : CollisionLayers,
+    pub mask         : CollisionLayers,
+    /// Effects run when touching an actor of the mask, `on_shot` ones when it is a projectile.
     pub on_collision : Vec::<Effect>,
+    pub on_shot      : Vec::<Effect>,
     //==
     pub on_start : Vec::<Effect>,
     pub on_tick  : Vec::<Effect>,
@@ -161,18 +229,23 @@ pub struct Actor {
 
 impl Actor {
     pub fn new(atype : ActorType, id : unit::Id) -> Actor {
+        let (layer, mask) = atype.default_layers();
         Actor {
             atype    : atype,
             id       : id,
             //==
-            transform: unit::Position{ x:0.0, y:0.0},
+            transform: unit::Transform::identity(),
             //==
             drawable : no_id(),
             drawctx  : DrawContext::WorldSpace,
             visible  : false,
             //==
             collision    : mk_nocol(),
+            local_aabb   : mk_nocol().get_ncol().local_aabb(),
+            layer,
+            mask,
             on_collision : Vec::<Effect>::new(),
+            on_shot      : Vec::<Effect>::new(),
             //==
             on_start  : Vec::<Effect>::new(),
             on_tick   : Vec::<Effect>::new(),
@@ -189,6 +262,65 @@ impl Actor {
         self.drawable.clone()
     }
 
+    pub fn collision(&self) -> &Collision{
+        &self.collision
+    }
+
+    /// Also caches the bounding box of the shape, the broadphase reads it every frame.
+    pub fn set_collision(&mut self, collision : Collision){
+        self.local_aabb = collision.get_ncol().local_aabb();
+        self.collision  = collision;
+    }
+
+    /// Where the collision shape sits in the world, as the narrow phase sees it.
+    pub fn collision_transform(&self) -> unit::Transform{
+        let size = self.collision.get_size();
+        let scale = self.transform.scale;
+        unit::Transform{
+            position : unit::Position{
+                x : self.transform.position.x + size.x * scale / 2.0,
+                y : self.transform.position.y + size.y * scale / 2.0,
+            },
+            ..self.transform
+        }
+    }
+
+    pub fn aabb(&self) -> ColAABB{
+        let t = self.collision_transform();
+        let scaled = ColAABB::new(self.local_aabb.mins() * t.scale, self.local_aabb.maxs() * t.scale);
+        scaled.transform_by(&t.isometry())
+    }
+
+    /// Box covering the actor whatever its rotation, for the actors turning in place.
+    /// It grows around the centre of the shape, which is where they turn.
+    pub fn reach_aabb(&self) -> ColAABB{
+        let t = self.collision_transform();
+        let scaled = ColAABB::new(self.local_aabb.mins() * t.scale, self.local_aabb.maxs() * t.scale);
+        let center = t.isometry() * scaled.center();
+        let radius = scaled.half_extents().norm();
+        ColAABB::new(center - nal::Vector2::repeat(radius), center + nal::Vector2::repeat(radius))
+    }
+
+    /// Ships, projectiles and pickups. The other actors stay where the level put them,
+    /// turning at most, and are only collided with.
+    pub fn moves(&self) -> bool{
+        match self.atype{
+            ActorType::Player | ActorType::Projectile => true,
+            _ => self.layer == LAYER_PICKUP
+        }
+    }
+
+    /// Whether touching `other` triggers effects on this actor.
+    pub fn reacts_to(&self, other : &Actor) -> bool{
+        self.mask & other.layer != LAYER_NONE
+    }
+
+    /// The effects touching `other` triggers, bound to it.
+    pub fn collision_effects(&self, other : &Actor) -> Vec::<Effect>{
+        let effs = if other.layer & LAYER_PROJECTILE != LAYER_NONE { &self.on_shot } else { &self.on_collision };
+        effs.iter().map(|e| e.bind_other(other.id)).collect()
+    }
+
     pub fn start(&mut self){      
         match self.atype{
             ActorType::Player => {
@@ -203,6 +335,10 @@ impl Actor {
                 self.visible = true;
                 self.ticking = true;
             },
+            ActorType::Projectile => {
+                self.visible = true;
+                self.ticking = true;
+            },
             _ => {
                 self.visible = true;
                 self.ticking = false;
@@ -221,9 +357,6 @@ impl Actor {
         // else if let ActorType::Background = self.atype {
         //     false
         // }
-        else if let ActorType::Player = self.atype {
-            false
-        }
         else if let Collision::NoCollision{..} = self.collision {
             false
         }  
@@ -233,3 +366,17 @@ impl Actor {
     }
 }
 
+
+
+/// A projectile flying forward from `position`, the terrain stops it.
+/// Its tick effects are in `on_tick`, the world picks them up when spawning it.
+pub fn make_projectile(position : unit::Position, drawable : Id) -> Actor {
+    let mut a = ActorType::Projectile.make();
+    a.transform.position = position;
+    a.add_drawable(drawable);
+    a.set_collision(mk_polycol(&mesh_gen::projectile(PROJECTILE_SIZE)));
+    a.on_tick.push(Effect::MoveActor{ actor_id : a.id, vector : unit::Position{ x : PROJECTILE_SPEED, y : 0.0 } });
+    a.on_tick.push(Effect::DespawnOffscreen);
+    a.on_collision.push(Effect::DespawnActor);
+    a
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use ncollide2d::bounding_volume::BoundingVolume;
+
+use crate::actors::ColAABB;
+use crate::unit::Id;
+
+/// Width of a grid column, in world units.
+pub const CELL_WIDTH : f32 = 256.0;
+
+/// Uniform grid over the x-extent of a world.
+/// Levels are long corridors, so columns along x are enough to keep
+/// the narrow phase to the actors around the one being tested.
+pub struct XGrid{
+    columns : Vec::<Vec::<usize>>,
+    entries : Vec::<(Id, ColAABB)>,
+    /// Entry of each actor, to take it out of its columns.
+    index   : HashMap::<Id, usize>,
+}
+
+impl XGrid{
+    pub fn new() -> Self{
+        XGrid{
+            columns : Vec::<Vec::<usize>>::new(),
+            entries : Vec::<(Id, ColAABB)>::new(),
+            index   : HashMap::<Id, usize>::new(),
+        }
+    }
+
+    /// Empties the grid and sizes it for a world `world_width` long.
+    /// Allocations are kept from one frame to the next.
+    pub fn reset(&mut self, world_width : f32){
+        let nb_columns = ((world_width / CELL_WIDTH).ceil() as usize).max(1);
+        self.columns.resize_with(nb_columns, Vec::<usize>::new);
+        for c in &mut self.columns{
+            c.clear();
+        }
+        self.entries.clear();
+        self.index.clear();
+    }
+
+    /// Columns covered by `[min_x, max_x]`, anything outside the world lands on the borders.
+    fn column_range(&self, min_x : f32, max_x : f32) -> (usize, usize){
+        let last = self.columns.len() - 1;
+        let to_column = |x : f32| ((x / CELL_WIDTH).floor().max(0.0) as usize).min(last);
+        (to_column(min_x), to_column(max_x))
+    }
+
+    pub fn insert(&mut self, id : Id, aabb : ColAABB){
+        let (first, last) = self.column_range(aabb.mins().x, aabb.maxs().x);
+        let entry_idx = self.entries.len();
+        self.entries.push((id, aabb));
+        self.index.insert(id, entry_idx);
+        for c in &mut self.columns[first..=last]{
+            c.push(entry_idx);
+        }
+    }
+
+    /// Takes the actor out of the grid, if it is in.
+    pub fn remove(&mut self, id : &Id){
+        let entry_idx = match self.index.remove(id){
+            Some(entry_idx) => entry_idx,
+            None => return
+        };
+        let aabb = &self.entries[entry_idx].1;
+        let (first, last) = self.column_range(aabb.mins().x, aabb.maxs().x);
+        for c in &mut self.columns[first..=last]{
+            c.retain(|e| *e != entry_idx);
+        }
+    }
+
+    /// Fills `out` with the actors whose box touches `aabb` grown by `margin`, each one once.
+    pub fn query(&self, aabb : &ColAABB, margin : f32, out : &mut Vec::<Id>){
+        out.clear();
+        if self.columns.is_empty(){
+            return;
+        }
+        let aabb = aabb.loosened(margin);
+        let (first, last) = self.column_range(aabb.mins().x, aabb.maxs().x);
+
+        // an entry spanning several columns is only reported from the first one queried.
+        for (k, c) in self.columns[first..=last].iter().enumerate(){
+            let column = first + k;
+            for e in c{
+                let (id, entry_aabb) = &self.entries[*e];
+                let (entry_first, _) = self.column_range(entry_aabb.mins().x, entry_aabb.maxs().x);
+                if entry_first.max(first) == column && entry_aabb.intersects(&aabb){
+                    out.push(*id);
+                }
+            }
+        }
+    }
+}
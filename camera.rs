@@ -0,0 +1,105 @@
+use serde::{Serialize, Deserialize};
+
+use crate::unit::*;
+
+/// Trauma lost each second, a full shake lasts a second.
+const TRAUMA_DECAY : f32 = 1.0;
+/// Offset of the view at full trauma, in units.
+const MAX_SHAKE    : f32 = 12.0;
+
+/// How the view moves through the world.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum CameraMode{
+    /// The view stays where it is placed.
+    #[default]
+    Fixed,
+    /// The view moves right by `speed` units per second.
+    AutoScroll{ speed : f32 },
+    /// The view keeps the target in a `dead_zone` (width, height) box around its center,
+    /// `smoothing` is how fast it catches up, per second.
+    Follow{ dead_zone : (f32, f32), smoothing : f32 },
+}
+
+/// The view of a world. The camera actor mirrors it so effects can read where the view is,
+/// the shake only moves the picture, not the view the game plays in.
+pub struct Camera{
+    pub mode : CameraMode,
+    /// World position of the bottom left corner of the view.
+    origin : Position,
+    view   : Size,
+    /// 0 to 1, the shake grows with its square.
+    trauma : f32,
+    /// Seconds since the camera started, drives the shake.
+    time   : f32,
+}
+
+impl Camera{
+    pub fn new(mode : CameraMode, view : Size) -> Self{
+        Camera{
+            mode,
+            origin : Origin,
+            view,
+            trauma : 0.0,
+            time   : 0.0,
+        }
+    }
+
+    pub fn origin(&self) -> Position{
+        self.origin
+    }
+
+    /// Size of the view, in units.
+    pub fn view(&self) -> Size{
+        self.view
+    }
+
+    pub fn place(&mut self, origin : Position){
+        self.origin = origin;
+    }
+
+    pub fn add_trauma(&mut self, amount : f32){
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    /// Moves the view `dt` seconds later, `target` is followed in follow mode.
+    /// The view never leaves a world of size `bounds`.
+    pub fn update(&mut self, dt : f32, target : Option<Position>, bounds : &Size){
+        self.time  += dt;
+        self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+
+        match self.mode {
+            CameraMode::Fixed => (),
+            CameraMode::AutoScroll{ speed } => {
+                self.origin.x += speed * dt;
+            },
+            CameraMode::Follow{ dead_zone, smoothing } => {
+                if let Some(target) = target {
+                    // distance of the target out of the dead zone, on each axis.
+                    let outside = |delta : f32, zone : f32| {
+                        let half = zone / 2.0;
+                        if delta.abs() > half { delta - half * delta.signum() } else { 0.0 }
+                    };
+                    let dx = outside(target.x - (self.origin.x + self.view.x / 2.0), dead_zone.0);
+                    let dy = outside(target.y - (self.origin.y + self.view.y / 2.0), dead_zone.1);
+                    let catch_up = 1.0 - (-smoothing * dt).exp();
+                    self.origin.x += dx * catch_up;
+                    self.origin.y += dy * catch_up;
+                }
+            }
+        }
+
+        if self.mode != CameraMode::Fixed {
+            self.origin.x = self.origin.x.min(bounds.x - self.view.x).max(0.0);
+            self.origin.y = self.origin.y.min(bounds.y - self.view.y).max(0.0);
+        }
+    }
+
+    /// Where the picture is drawn from, the view moved by the shake.
+    pub fn shaken_origin(&self) -> Position{
+        let amplitude = MAX_SHAKE * self.trauma * self.trauma;
+        Position{
+            x : self.origin.x + amplitude * (self.time * 37.0).sin(),
+            y : self.origin.y + amplitude * (self.time * 53.0 + 1.7).sin(),
+        }
+    }
+}
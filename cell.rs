@@ -1,7 +1,6 @@
 use std::f64::consts::{PI, FRAC_PI_2};
 
 use rand::Rng;
-use rand::rngs::ThreadRng;
 use nalgebra as nal;
 use nal::{Vector3, Rotation3};
 
@@ -262,7 +261,7 @@ impl Cell {
         return a && b && c && d;
     }
 
-    pub fn place_at_bottom(&self, rng :&mut ThreadRng) -> Position  {
+    pub fn place_at_bottom(&self, rng :&mut GameRng) -> Position  {
         let x = rng.gen_range(0.0, 1.0);
         let y = 0.0;        
         self.get_point(x, y)    
@@ -283,7 +282,7 @@ fn on_bisector_at(p: &Point2, vy : &Vector2, vx: &Vector2, dist: f32) -> Point2{
 
 
 
-pub fn place_disc_in_cell(cell : &Cell, rng :&mut ThreadRng) -> Position  {
+pub fn place_disc_in_cell(cell : &Cell, rng :&mut GameRng) -> Position  {
     let x = rng.gen_range(0.0, 1.0);
     let y = rng.gen_range(0.0, 1.0);
     cell.get_point(x, y)    
@@ -3,27 +3,57 @@ use crate::unit::*;
 use crate::render;
 use crate::player_handle_input;
 use crate::GameState;
-use crate::color;
 use crate::actors;
 use crate::level;
+use crate::highscore;
+use crate::menu;
+use crate::flight;
 use crate::{Systems};
-use ggez::timer;
-
-use ggez::audio::{SoundSource};
-use ggez::{Context};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Effect{
     PlaceActor{actor_id: Id, position: Position},    
+    /// Moves the actor by `vector` units per second.
     MoveActor{actor_id: Id, vector: Position},
-    UpdateScore{actor_id: Id},
+    /// Shows the score of the player `pilot`.
+    UpdateScore{actor_id: Id, pilot : usize},
     SetScore{new_value : i32},
-    ProcessInput,
+    /// Flies the actor with the input of the player `pilot`, `velocity` is kept from one step to the next.
+    ProcessInput{model : flight::FlightModel, velocity : Vector2, pilot : usize},
+    /// Takes the pickup, the ship touching it scores. Its id is bound when the collision happens.
     KillActor{actor_id: Id},
     ResetActor{actor_id: Id},
     // NextScene{cur_scene_idx : usize, next_scene_idx : usize},
     AutoNextScene{ duration : f32, cur_scene_idx : Id, next_scene_idx : Id},
+    /// Plays a variant of the sound group of the mixer.
     PlaySound(usize),
+    /// Spawns a projectile at `muzzle` (relative to the actor) while the player `pilot` holds fire,
+    /// at most once every `cooldown` seconds, playing the sound group `sound` if any.
+    Fire{ drawable : Id, muzzle : Position, cooldown : f32, timer : f32, pilot : usize, sound : Option<usize>},
+    DespawnActor,
+    DespawnOffscreen,
+    /// Removes the actor touched, its id is bound when the collision happens.
+    DespawnOther{actor_id: Id},
+    /// Edits the name shown by the actor, then saves the last run under it and leaves the scene.
+    EnterName{ entry : highscore::NameEntry, next_scene_idx : Id},
+    /// Runs the menu `menu_id` of the systems, its items are drawn by other actors.
+    Menu{menu_id : Id},
+    /// Turns the actor by `speed` radians per second.
+    Spin{speed : f32},
+    /// Rocks the actor back and forth, `amplitude` radians around its rest angle.
+    Sway{amplitude : f32, speed : f32, time : f32},
+    /// The ship `actor_id` touched something harmful, the world takes the shield or a life of its player.
+    HitPlayer{actor_id: Id},
+    /// Shakes the camera, `trauma` between 0 and 1.
+    Shake{trauma : f32},
+    /// The actor blinks every `blink` seconds and ignores the hazards for `time` seconds,
+    /// then gets its `mask` back.
+    Invulnerable{time : f32, blink : f32, mask : actors::CollisionLayers},
+    /// Shows the lives and the shield of the player `pilot`.
+    UpdateLives{pilot : usize},
+    UpdateShield{pilot : usize},
+    /// Shows the prompt template of the actor text with the inputs of the device played last.
+    UpdatePrompt,
 }
 
 impl Effect{
@@ -36,62 +66,63 @@ impl Effect{
         }        
     } 
 
-    pub fn on_actor(&mut self, actor : &mut actors::Actor, _ctx: &Context, state : &GameState, worldbounds : &level::WorldBounds, systems : &mut Systems) -> Option::<level::WorldChange>{        
+    /// Hands the actor touched to the effects that need it.
+    pub fn bind_other(&self, other : Id) -> Effect{
+        match self {
+            Effect::DespawnOther{..} => Effect::DespawnOther{actor_id: other},
+            Effect::KillActor{..}    => Effect::KillActor{actor_id: other},
+            _ => *self
+        }
+    }
+
+    /// Whether the effect turns the actor it ticks on.
+    pub fn turns(&self) -> bool{
+        matches!(self, Effect::Spin{..} | Effect::Sway{..})
+    }
+
+    pub fn on_actor(&mut self, actor : &mut actors::Actor, dt : f32, state : &GameState, worldbounds : &level::WorldBounds, systems : &mut Systems) -> Option::<level::WorldChange>{        
         match self {
             Effect::AutoNextScene{duration, cur_scene_idx, next_scene_idx} => {
-                *duration -= timer::delta(_ctx).as_secs_f32();
+                *duration -= dt;
                 if *duration < 0.0 {
                     let levelchange = level::WorldChange {
-                        score: 0,
                         level: Some(next_scene_idx.clone()),
-                        dead_effect: false
+                        ..level::WorldChange::default()
                     };
                     return Some(levelchange);                
                 }
                 None                
             },
             Effect::KillActor{actor_id} => {                
-                // if let render::Renderable::DynamicRect{ref mut color, ..} = _actor.drawable {
-                //     *color = color::GREEN;
-                // }      
-                let id = actor.get_drawable();
-                if let Some(mm) = systems.renderer_source.meshmodels.get_mut(&id){
-                    mm.polygons.get_mut(0).map( |p|{p.color = color::GREEN} );
-                }
-
-                // if let render::Renderable::DynamicPoly{poly_idx, mesh_oidx, ref mut dirty} = actor.get_drawable() {
-                //     systems.renderer.polygons.get_mut(*poly_idx).map(|poly| {
-                //         poly.color = color::GREEN;
-                //         poly
-                //     });
-                //     *dirty = true;
-                // }                 
                 actor.ticking = false;       
-                actor.collision = actors::mk_nocol();         
+                actor.set_collision(actors::mk_nocol());         
                 Some(level::WorldChange {
-                    score: 1,
-                    level: None,
-                    dead_effect: true
+                    scores: vec![(*actor_id, 1)],
+                    dead_effect: true,
+                    despawn: true,
+                    ..level::WorldChange::default()
                 })
             },
-            Effect::ProcessInput => {         
-                player_handle_input(&state.input, actor, &worldbounds, timer::delta(_ctx).as_millis());
+            Effect::ProcessInput{model, velocity, pilot} => {         
+                let input = state.pilots.get(*pilot)?.input;
+                player_handle_input(&input, model, velocity, actor, &worldbounds, dt);
                 None
             },
             Effect::MoveActor{actor_id, vector} => {                
-                actor.transform.x += vector.x;
-                actor.transform.y += vector.y;                                    
+                actor.transform.position.x += vector.x * dt;
+                actor.transform.position.y += vector.y * dt;                                    
                 None
             },
             Effect::PlaceActor{actor_id, position} => {                
-                actor.transform = *position;
+                actor.transform.position = *position;
                 None
             },
-            Effect::UpdateScore{actor_id} => {                
+            Effect::UpdateScore{actor_id, pilot} => {                
                 
                 let id = actor.get_drawable();
+                let score = state.pilots.get(*pilot)?.score;
                 if let Some(tm) = systems.renderer_source.textmodels.get_mut(&id){
-                    tm.update_string(format!( "Score: {}", state.score));
+                    tm.update_string(format!( "{}Score: {}", state.pilot_tag(*pilot), score));
                 }
 
                 // if let render::Renderable::DynamicTextDraw{string, ..} = &mut actor.get_drawable(){
@@ -99,15 +130,193 @@ impl Effect{
                 // }
                 None
             },
-            Effect::PlaySound(sound_index) => {
-                let s = systems.sounds.get_mut(*sound_index).unwrap();
-                let _ = s.play();      
+            Effect::PlaySound(group_idx) => {
+                systems.mixer.play(*group_idx);
                 Some(level::WorldChange {
-                    score: 0,
-                    level: None,
-                    dead_effect: true
+                    dead_effect: true,
+                    ..level::WorldChange::default()
                 })          
             },
+            Effect::Fire{drawable, muzzle, cooldown, timer, pilot, sound} => {
+                *timer -= dt;
+                if !state.pilots.get(*pilot)?.input.fire || *timer > 0.0 {
+                    return None;
+                }
+                *timer = *cooldown;
+                if let Some(group) = sound {
+                    systems.mixer.play(*group);
+                }
+                let position = Position{
+                    x: actor.transform.position.x + muzzle.x,
+                    y: actor.transform.position.y + muzzle.y
+                };
+                Some(level::WorldChange {
+                    spawn: Some(actors::make_projectile(position, *drawable)),
+                    ..level::WorldChange::default()
+                })
+            },
+            Effect::DespawnActor => {
+                actor.visible = false;
+                actor.stop();
+                Some(level::WorldChange {
+                    despawn: true,
+                    ..level::WorldChange::default()
+                })
+            },
+            Effect::DespawnOffscreen => {
+                let left  = worldbounds.min.x;
+                let right = left + state.screen.x;
+                let p = actor.transform.position;
+                if p.x < left || p.x > right || p.y < 0.0 || p.y > worldbounds.max.y {
+                    actor.visible = false;
+                    actor.stop();
+                    return Some(level::WorldChange {
+                        despawn: true,
+                        ..level::WorldChange::default()
+                    });
+                }
+                None
+            },
+            Effect::DespawnOther{actor_id} => {
+                Some(level::WorldChange {
+                    dead_effect: true,
+                    despawn_other: Some(*actor_id),
+                    ..level::WorldChange::default()
+                })
+            },
+            Effect::EnterName{entry, next_scene_idx} => {
+                let confirmed = entry.update(&state.input, dt);
+                if let Some(tm) = systems.renderer_source.textmodels.get_mut(&actor.get_drawable()){
+                    let display = entry.display();
+                    if tm.string != display {
+                        tm.update_string(display);
+                    }
+                }
+                if !confirmed {
+                    return None;
+                }
+                if let Some(run) = &state.last_run {
+                    systems.highscores.insert(highscore::HighscoreEntry::new(entry.name(), run));
+                    if let Err(e) = systems.highscores.save() {
+                        println!("Could not save highscores: {}", e);
+                    }
+                }
+                Some(level::WorldChange {
+                    level: Some(*next_scene_idx),
+                    ..level::WorldChange::default()
+                })
+            },
+            Effect::Menu{menu_id} => {
+                let m = systems.menus.get_mut(menu_id)?;
+                match m.update(&state.input, dt, &mut systems.renderer_source)? {
+                    menu::MenuAction::Goto(next_scene_idx) => Some(level::WorldChange {
+                        level: Some(next_scene_idx),
+                        ..level::WorldChange::default()
+                    }),
+                    menu::MenuAction::NewRun{next_scene_idx, players} => Some(level::WorldChange {
+                        level: Some(next_scene_idx),
+                        players: Some(players),
+                        ..level::WorldChange::default()
+                    }),
+                    menu::MenuAction::Quit => Some(level::WorldChange {
+                        quit: true,
+                        ..level::WorldChange::default()
+                    }),
+                    menu::MenuAction::Resume => Some(level::WorldChange {
+                        resume: true,
+                        ..level::WorldChange::default()
+                    }),
+                    menu::MenuAction::Restart => Some(level::WorldChange {
+                        restart: true,
+                        ..level::WorldChange::default()
+                    }),
+                    menu::MenuAction::Change{setting, step} => {
+                        let label = m.selected_label();
+                        systems.settings.adjust(setting, step);
+                        if let Some(tm) = systems.renderer_source.textmodels.get_mut(&label){
+                            tm.update_string(systems.settings.describe(setting));
+                        }
+                        Some(level::WorldChange {
+                            settings: true,
+                            ..level::WorldChange::default()
+                        })
+                    },
+                    menu::MenuAction::Rebind{player, control} => {
+                        let label = m.selected_label();
+                        Some(level::WorldChange {
+                            rebind: Some((player, control, label)),
+                            ..level::WorldChange::default()
+                        })
+                    },
+                    menu::MenuAction::Setting(_) => None
+                }
+            },
+            Effect::Spin{speed} => {
+                actor.transform.rotation += *speed * dt;
+                None
+            },
+            Effect::Sway{amplitude, speed, time} => {
+                *time += dt;
+                actor.transform.rotation = *amplitude * (*speed * *time).sin();
+                None
+            },
+            Effect::HitPlayer{actor_id} => {
+                Some(level::WorldChange {
+                    hits: vec![*actor_id],
+                    dead_effect: true,
+                    ..level::WorldChange::default()
+                })
+            },
+            Effect::Shake{trauma} => {
+                Some(level::WorldChange {
+                    shake: *trauma,
+                    dead_effect: true,
+                    ..level::WorldChange::default()
+                })
+            },
+            Effect::Invulnerable{time, blink, mask} => {
+                *time -= dt;
+                if *time <= 0.0 {
+                    actor.visible = true;
+                    actor.mask    = *mask;
+                    return Some(level::WorldChange {
+                        dead_effect: true,
+                        ..level::WorldChange::default()
+                    });
+                }
+                actor.visible = (*time / *blink) as i32 % 2 == 0;
+                actor.mask    = *mask & !actors::LAYER_HAZARDS;
+                None
+            },
+            Effect::UpdateLives{pilot} => {
+                let lives = state.pilots.get(*pilot)?.lives;
+                if let Some(tm) = systems.renderer_source.textmodels.get_mut(&actor.get_drawable()){
+                    let display = format!("Lives: {}", lives.max(0));
+                    if tm.string != display {
+                        tm.update_string(display);
+                    }
+                }
+                None
+            },
+            Effect::UpdateShield{pilot} => {
+                if let Some(tm) = systems.renderer_source.textmodels.get_mut(&actor.get_drawable()){
+                    let display = format!("Shield: {}", state.shield_meter(*pilot));
+                    if tm.string != display {
+                        tm.update_string(display);
+                    }
+                }
+                None
+            },
+            Effect::UpdatePrompt => {
+                let id = actor.get_drawable();
+                let display = systems.settings.input.format_prompt(systems.prompts.get(&id)?, systems.prompt_style);
+                if let Some(tm) = systems.renderer_source.textmodels.get_mut(&id){
+                    if tm.string != display {
+                        tm.update_string(display);
+                    }
+                }
+                None
+            },
             _ => None
         }
         
@@ -0,0 +1,53 @@
+use serde::{Serialize, Deserialize};
+
+use crate::unit::*;
+use crate::InputState;
+
+/// Speed of the ship when the axes move it directly, in units per second.
+const DIRECT_SPEED : f32 = 180.5;
+
+/// How the input moves the ship, relative to the scrolling.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum FlightModel{
+    /// The axes set the speed of the ship, it stops with them.
+    #[default]
+    Direct,
+    /// The axes push the ship, which keeps its speed until the drag and the gravity slow it down.
+    /// `thrust` and `gravity` are in units per second squared, `drag` is the part
+    /// of the speed lost each second.
+    Inertia{ thrust : f32, drag : f32, max_speed : f32, gravity : f32 },
+}
+
+impl FlightModel{
+    /// The inertia model the settings switch to.
+    pub fn inertia() -> Self{
+        FlightModel::Inertia{ thrust : 900.0, drag : 1.5, max_speed : 300.0, gravity : 120.0 }
+    }
+
+    /// Updates `velocity` from the input, `dt` seconds later.
+    pub fn apply(&self, input : &InputState, velocity : &mut Vector2, dt : f32){
+        let axes = Vector2::new(input.xaxis, input.yaxis);
+        match *self {
+            FlightModel::Direct => {
+                *velocity = axes * DIRECT_SPEED;
+            },
+            FlightModel::Inertia{ thrust, drag, max_speed, gravity } => {
+                *velocity += axes * thrust * dt;
+                velocity.y -= gravity * dt;
+                *velocity *= (1.0 - drag * dt).max(0.0);
+                let speed = velocity.norm();
+                if speed > max_speed {
+                    *velocity *= max_speed / speed;
+                }
+            }
+        }
+    }
+
+    /// Speed the ship banks fully at.
+    pub fn max_speed(&self) -> f32{
+        match *self {
+            FlightModel::Direct => DIRECT_SPEED,
+            FlightModel::Inertia{ max_speed, .. } => max_speed
+        }
+    }
+}
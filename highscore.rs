@@ -0,0 +1,188 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+
+use crate::InputState;
+use crate::menu::{MenuInput, MenuEvent};
+
+/// Entries kept in the table.
+pub const MAX_ENTRIES : usize = 10;
+/// Letters in a player name.
+pub const NAME_LENGTH : usize = 3;
+const NAME_ALPHABET   : &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 ";
+
+/// What is left of a run once the game is over.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FinishedRun{
+    pub score : i32,
+    pub level : i32,
+    pub seed  : u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighscoreEntry{
+    pub name  : String,
+    pub score : i32,
+    pub level : i32,
+    pub seed  : u64,
+    /// Seconds since the unix epoch.
+    pub date  : u64,
+}
+
+impl HighscoreEntry{
+    pub fn new(name : String, run : &FinishedRun) -> Self{
+        HighscoreEntry{
+            name,
+            score : run.score,
+            level : run.level,
+            seed  : run.seed,
+            date  : SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        }
+    }
+}
+
+/// The best scores, highest first.
+/// Saved after each insertion when a file is set, headless runs have none.
+pub struct Highscores{
+    entries : Vec::<HighscoreEntry>,
+    path    : Option<PathBuf>,
+    /// Rank of the last entry inserted, to highlight it.
+    pub last_rank : Option<usize>,
+}
+
+impl Highscores{
+    pub fn new() -> Self{
+        Highscores{
+            entries   : Vec::<HighscoreEntry>::new(),
+            path      : None,
+            last_rank : None,
+        }
+    }
+
+    /// A missing or unreadable file gives an empty table, saved to `path` later on.
+    pub fn load(path : PathBuf) -> Self{
+        let mut highscores = Highscores::new();
+        match fs::read_to_string(&path){
+            Ok(s) => match ron::de::from_str::<Vec::<HighscoreEntry>>(&s){
+                Ok(entries) => highscores.entries = entries,
+                Err(e)      => println!("Invalid highscore file {}: {}", path.display(), e)
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => println!("Could not read highscore file {}: {}", path.display(), e)
+        }
+        highscores.entries.sort_by_key(|e| Reverse(e.score));
+        highscores.entries.truncate(MAX_ENTRIES);
+        highscores.path = Some(path);
+        highscores
+    }
+
+    pub fn entries(&self) -> &[HighscoreEntry]{
+        &self.entries
+    }
+
+    pub fn qualifies(&self, score : i32) -> bool{
+        if score <= 0 {
+            return false;
+        }
+        match self.entries.get(MAX_ENTRIES - 1){
+            Some(last) => score > last.score,
+            None       => true
+        }
+    }
+
+    /// Ties keep the oldest entry first.
+    pub fn insert(&mut self, entry : HighscoreEntry) -> Option<usize>{
+        let rank = self.entries.iter().position(|e| e.score < entry.score).unwrap_or(self.entries.len());
+        self.last_rank = None;
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        self.last_rank = Some(rank);
+        self.last_rank
+    }
+
+    pub fn save(&self) -> io::Result<()>{
+        let path = match &self.path{
+            Some(path) => path,
+            None       => return Ok(())
+        };
+        if let Some(dir) = path.parent(){
+            fs::create_dir_all(dir)?;
+        }
+        let s = ron::ser::to_string_pretty(&self.entries, ron::ser::PrettyConfig::default()).unwrap();
+        fs::write(path, s)
+    }
+}
+
+/// `YYYY-MM-DD` of a unix timestamp, in UTC.
+pub fn format_date(timestamp : u64) -> String{
+    // days to civil date, from Howard Hinnant's algorithms.
+    let z   = (timestamp / 86400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp  = (5 * doy + 2) / 153;
+    let day   = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year  = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Arcade style name entry : up and down pick the letter under the cursor,
+/// left and right move the cursor, back moves it left too, fire confirms.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NameEntry{
+    letters : [u8; NAME_LENGTH],
+    cursor  : usize,
+    input   : MenuInput,
+}
+
+impl NameEntry{
+    pub fn new() -> Self{
+        NameEntry{
+            letters : [0; NAME_LENGTH],
+            cursor  : 0,
+            input   : MenuInput::new(),
+        }
+    }
+
+    /// Returns true once the name is confirmed.
+    pub fn update(&mut self, input : &InputState, dt : f32) -> bool{
+        let nb_letters = NAME_ALPHABET.len() as u8;
+        let letter = &mut self.letters[self.cursor];
+        match self.input.update(input, dt){
+            Some(MenuEvent::Confirm) => return true,
+            Some(MenuEvent::Up)      => *letter = (*letter + 1) % nb_letters,
+            Some(MenuEvent::Down)    => *letter = (*letter + nb_letters - 1) % nb_letters,
+            Some(MenuEvent::Right)   => self.cursor = (self.cursor + 1).min(NAME_LENGTH - 1),
+            Some(MenuEvent::Left) | Some(MenuEvent::Back) => self.cursor = self.cursor.saturating_sub(1),
+            None => ()
+        }
+        false
+    }
+    pub fn name(&self) -> String{
+        let name : String = self.letters.iter().map(|l| NAME_ALPHABET[*l as usize] as char).collect();
+        name.trim_end().to_string()
+    }
+
+    /// The letters, the one under the cursor between brackets.
+    pub fn display(&self) -> String{
+        let mut parts = Vec::<String>::new();
+        for (i, l) in self.letters.iter().enumerate(){
+            let c = NAME_ALPHABET[*l as usize] as char;
+            if i == self.cursor {
+                parts.push(format!("[{}]", c));
+            } else {
+                parts.push(format!(" {} ", c));
+            }
+        }
+        parts.concat()
+    }
+}
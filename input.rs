@@ -0,0 +1,491 @@
+use std::collections::{HashMap, HashSet};
+
+use ggez::event::{Axis, Button};
+use ggez::input::gamepad::GamepadId;
+use ggez::input::keyboard::KeyCode;
+use serde::{Serialize, Deserialize};
+
+use crate::InputState;
+
+/// Past this the stick would barely move the ship.
+pub const MAX_DEADZONE : f32 = 0.9;
+/// Digital actions are held past this value.
+pub const PRESS_THRESHOLD  : f32 = 0.5;
+
+/// Keys that can be bound, the settings file names them as they are spelled here.
+const BINDABLE_KEYS : &[KeyCode] = &[
+    KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right,
+    KeyCode::Space, KeyCode::Return, KeyCode::Escape, KeyCode::Back, KeyCode::Tab,
+    KeyCode::LShift, KeyCode::RShift, KeyCode::LControl, KeyCode::RControl, KeyCode::LAlt, KeyCode::RAlt,
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+    KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+    KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+    KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+    KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+];
+
+const BINDABLE_BUTTONS : &[Button] = &[
+    Button::South, Button::East, Button::North, Button::West, Button::C, Button::Z,
+    Button::LeftTrigger, Button::LeftTrigger2, Button::RightTrigger, Button::RightTrigger2,
+    Button::Select, Button::Start, Button::Mode, Button::LeftThumb, Button::RightThumb,
+    Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight,
+];
+
+const BINDABLE_AXES : &[Axis] = &[
+    Axis::LeftStickX, Axis::LeftStickY, Axis::LeftZ,
+    Axis::RightStickX, Axis::RightStickY, Axis::RightZ,
+    Axis::DPadX, Axis::DPadY,
+];
+
+/// What the game reads from the devices.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action{
+    MoveX,
+    MoveY,
+    Fire,
+    Pause,
+    /// Picks the selected menu item, fire does too.
+    Confirm,
+    /// Leaves a menu by its back item.
+    Back,
+    Fullscreen,
+    Screenshot,
+}
+
+impl Action{
+    pub const ALL : [Action; 8] = [
+        Action::MoveX,
+        Action::MoveY,
+        Action::Fire,
+        Action::Pause,
+        Action::Confirm,
+        Action::Back,
+        Action::Fullscreen,
+        Action::Screenshot,
+    ];
+}
+
+/// Where an input comes from, each gamepad apart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Device{
+    Keyboard,
+    Gamepad(GamepadId),
+}
+
+/// How the prompts name the inputs, after the device used last.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PromptStyle{
+    Keyboard,
+    Gamepad,
+}
+
+/// A key, gamepad button or gamepad axis, by name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Source{
+    Key(String),
+    Button(String),
+    Axis(String),
+}
+
+impl Source{
+    pub fn key(key : KeyCode) -> Self{
+        Source::Key(format!("{:?}", key))
+    }
+
+    pub fn button(button : Button) -> Self{
+        Source::Button(format!("{:?}", button))
+    }
+
+    pub fn axis(axis : Axis) -> Self{
+        Source::Axis(format!("{:?}", axis))
+    }
+
+    /// False for names that are not a bindable key, button or axis.
+    pub fn is_known(&self) -> bool{
+        match self {
+            Source::Key(name)    => BINDABLE_KEYS.iter().any(|k| format!("{:?}", k) == *name),
+            Source::Button(name) => BINDABLE_BUTTONS.iter().any(|b| format!("{:?}", b) == *name),
+            Source::Axis(name)   => BINDABLE_AXES.iter().any(|a| format!("{:?}", a) == *name),
+        }
+    }
+
+    /// How the prompts show the source : keys by name, buttons as printed on a pad.
+    pub fn glyph(&self) -> String{
+        let name = match self {
+            Source::Key(name) => return name.clone(),
+            Source::Button(name) | Source::Axis(name) => name.as_str()
+        };
+        let glyph = match name {
+            "South" => "(A)",
+            "East"  => "(B)",
+            "West"  => "(X)",
+            "North" => "(Y)",
+            "LeftTrigger"   => "LB",
+            "RightTrigger"  => "RB",
+            "LeftTrigger2"  => "LT",
+            "RightTrigger2" => "RT",
+            "LeftThumb"     => "L3",
+            "RightThumb"    => "R3",
+            "LeftStickX"  | "LeftStickY"  => "L-stick",
+            "RightStickX" | "RightStickY" => "R-stick",
+            "DPadX" | "DPadY" => "D-pad",
+            "DPadUp"    => "D-pad Up",
+            "DPadDown"  => "D-pad Down",
+            "DPadLeft"  => "D-pad Left",
+            "DPadRight" => "D-pad Right",
+            other => other
+        };
+        glyph.to_string()
+    }
+
+    fn style(&self) -> PromptStyle{
+        match self {
+            Source::Key(_) => PromptStyle::Keyboard,
+            _ => PromptStyle::Gamepad
+        }
+    }
+
+    fn same_device(&self, other : &Source) -> bool{
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// A source driving an action : the action reads `scale` when a key or button is held,
+/// and the axis value times `scale` for an axis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding{
+    pub source : Source,
+    pub scale  : f32,
+}
+
+impl Binding{
+    fn new(source : Source, scale : f32) -> Self{
+        Binding{ source, scale }
+    }
+}
+
+/// Every source of an action.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionMap{
+    pub action   : Action,
+    pub bindings : Vec::<Binding>,
+    /// Axis values closer to the center than this are read as 0.
+    #[serde(default)]
+    pub deadzone : f32,
+}
+
+/// A line of the controls scene : the sources pushing `action` toward the sign of `scale`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Control{
+    pub action : Action,
+    pub scale  : f32,
+    pub label  : &'static str,
+}
+
+/// What the controls scene lets the player rebind.
+pub const CONTROLS : [Control; 8] = [
+    Control{ action : Action::MoveY,   scale :  1.0, label : "Up" },
+    Control{ action : Action::MoveY,   scale : -1.0, label : "Down" },
+    Control{ action : Action::MoveX,   scale : -1.0, label : "Left" },
+    Control{ action : Action::MoveX,   scale :  1.0, label : "Right" },
+    Control{ action : Action::Fire,    scale :  1.0, label : "Fire" },
+    Control{ action : Action::Pause,   scale :  1.0, label : "Pause" },
+    Control{ action : Action::Confirm, scale :  1.0, label : "Confirm" },
+    Control{ action : Action::Back,    scale :  1.0, label : "Back" },
+];
+
+/// The bindings of every action, saved with the settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputMap{
+    pub actions : Vec::<ActionMap>,
+}
+
+impl Default for InputMap{
+    fn default() -> Self{
+        let key    = |k : KeyCode, scale : f32| Binding::new(Source::key(k), scale);
+        let button = |b : Button, scale : f32| Binding::new(Source::button(b), scale);
+        let axis   = |a : Axis, scale : f32| Binding::new(Source::axis(a), scale);
+        let map = |action : Action, bindings : Vec::<Binding>, deadzone : f32| ActionMap{ action, bindings, deadzone };
+        InputMap{
+            actions : vec![
+                // some pads report the d-pad as buttons, others as axes.
+                map(Action::MoveX, vec![
+                    key(KeyCode::Left, -1.0), key(KeyCode::Right, 1.0), axis(Axis::LeftStickX, 1.0),
+                    button(Button::DPadLeft, -1.0), button(Button::DPadRight, 1.0), axis(Axis::DPadX, 1.0),
+                ], 0.2),
+                map(Action::MoveY, vec![
+                    key(KeyCode::Up, 1.0), key(KeyCode::Down, -1.0), axis(Axis::LeftStickY, 1.0),
+                    button(Button::DPadUp, 1.0), button(Button::DPadDown, -1.0), axis(Axis::DPadY, 1.0),
+                ], 0.2),
+                map(Action::Fire, vec![key(KeyCode::Space, 1.0), button(Button::South, 1.0), button(Button::West, 1.0)], 0.0),
+                map(Action::Pause, vec![key(KeyCode::Escape, 1.0), button(Button::Start, 1.0)], 0.0),
+                map(Action::Confirm, vec![key(KeyCode::Return, 1.0), button(Button::South, 1.0)], 0.0),
+                map(Action::Back, vec![key(KeyCode::Escape, 1.0), key(KeyCode::Back, 1.0), button(Button::East, 1.0)], 0.0),
+                map(Action::Fullscreen, vec![key(KeyCode::F11, 1.0)], 0.0),
+                map(Action::Screenshot, vec![key(KeyCode::P, 1.0)], 0.0),
+            ]
+        }
+    }
+}
+
+impl InputMap{
+    /// The bindings of the second player : the left of the keyboard, and a gamepad of their own.
+    pub fn second_player() -> Self{
+        let mut map = InputMap::default();
+        let keys = [
+            (Action::MoveX, vec![(KeyCode::A, -1.0), (KeyCode::D, 1.0)]),
+            (Action::MoveY, vec![(KeyCode::W, 1.0), (KeyCode::S, -1.0)]),
+            (Action::Fire, vec![(KeyCode::LShift, 1.0)]),
+        ];
+        for m in &mut map.actions {
+            // the keys shared with the first player stay theirs.
+            m.bindings.retain(|b| !matches!(b.source, Source::Key(_)));
+            if let Some((_, bindings)) = keys.iter().find(|(action, _)| *action == m.action) {
+                for (key, scale) in bindings {
+                    m.bindings.push(Binding::new(Source::key(*key), *scale));
+                }
+            }
+        }
+        map
+    }
+
+    pub fn get(&self, action : Action) -> Option<&ActionMap>{
+        self.actions.iter().find(|m| m.action == action)
+    }
+
+    fn get_mut(&mut self, action : Action) -> Option<&mut ActionMap>{
+        self.actions.iter_mut().find(|m| m.action == action)
+    }
+
+    pub fn deadzone(&self, action : Action) -> f32{
+        self.get(action).map_or(0.0, |m| m.deadzone)
+    }
+
+    pub fn set_deadzone(&mut self, action : Action, deadzone : f32){
+        if let Some(m) = self.get_mut(action){
+            m.deadzone = deadzone.clamp(0.0, MAX_DEADZONE);
+        }
+    }
+
+    /// True when pushing the left stick up moves down.
+    pub fn stick_y_inverted(&self) -> bool{
+        let stick = Source::axis(Axis::LeftStickY);
+        self.get(Action::MoveY).is_some_and(|m| m.bindings.iter().any(|b| b.source == stick && b.scale < 0.0))
+    }
+
+    pub fn set_stick_y_inverted(&mut self, inverted : bool){
+        let stick = Source::axis(Axis::LeftStickY);
+        let scale = if inverted { -1.0 } else { 1.0 };
+        if let Some(m) = self.get_mut(Action::MoveY){
+            for b in m.bindings.iter_mut().filter(|b| b.source == stick) {
+                b.scale = scale;
+            }
+        }
+    }
+
+    /// The actions `source` is bound to.
+    pub fn actions_of(&self, source : &Source) -> Vec::<Action>{
+        self.actions.iter()
+            .filter(|m| m.bindings.iter().any(|b| b.source == *source))
+            .map(|m| m.action)
+            .collect()
+    }
+
+    /// Drops the unknown sources, adds the actions missing from the file
+    /// with their bindings in `defaults` and brings the deadzones back in range.
+    pub fn validate(&mut self, defaults : &InputMap, problems : &mut Vec::<String>){
+        for m in &mut self.actions {
+            let action = m.action;
+            m.bindings.retain(|b| {
+                let known = b.source.is_known();
+                if !known {
+                    problems.push(format!("{:?}: unknown input {:?}", action, b.source));
+                }
+                known
+            });
+            if !(0.0..=MAX_DEADZONE).contains(&m.deadzone) {
+                problems.push(format!("{:?}: deadzone {} is not between 0 and {}", m.action, m.deadzone, MAX_DEADZONE));
+                m.deadzone = defaults.deadzone(m.action);
+            }
+        }
+        for action in Action::ALL.iter() {
+            if self.get(*action).is_none() {
+                problems.push(format!("{:?} is not bound, using the default bindings", action));
+                self.actions.push(defaults.get(*action).unwrap().clone());
+            }
+        }
+    }
+
+    /// Binds `source` to the control, in place of its source of the same kind
+    /// (a key replaces a key, a button a button). What `source` drove before swaps
+    /// to the replaced source, or loses it when the control had none of that kind.
+    pub fn rebind(&mut self, control : &Control, source : Source){
+        let same_direction = |b : &Binding| b.scale.signum() == control.scale.signum();
+        let (target, replaced) = match self.get(control.action){
+            Some(m) => {
+                let target = m.bindings.iter().position(|b| same_direction(b) && b.source.same_device(&source));
+                (target, target.map(|idx| m.bindings[idx].source.clone()))
+            },
+            None => return
+        };
+        if replaced.as_ref() == Some(&source) {
+            return;
+        }
+        for m in &mut self.actions {
+            let own = if m.action == control.action { target } else { None };
+            let mut idx = 0;
+            m.bindings.retain_mut(|b| {
+                let is_target = Some(idx) == own;
+                idx += 1;
+                if is_target || b.source != source {
+                    return true;
+                }
+                match &replaced {
+                    Some(r) => { b.source = r.clone(); true },
+                    None    => false
+                }
+            });
+        }
+        let m = self.get_mut(control.action).unwrap();
+        match target {
+            Some(idx) => m.bindings[idx].source = source,
+            None      => m.bindings.push(Binding::new(source, control.scale)),
+        }
+    }
+
+    /// The line of the controls scene showing `control`.
+    pub fn describe(&self, control : &Control) -> String{
+        let names : Vec::<String> = self.get(control.action).map_or(Vec::new(), |m| {
+            m.bindings.iter()
+                .filter(|b| b.scale.signum() == control.scale.signum())
+                .filter(|b| !matches!(b.source, Source::Axis(_)))
+                .map(|b| b.source.glyph())
+                .collect()
+        });
+        if names.is_empty() {
+            format!("{}: -", control.label)
+        } else {
+            format!("{}: {}", control.label, names.join(" / "))
+        }
+    }
+
+    /// How the prompts show `action` : its first source for the style,
+    /// both directions for the moves (`Up/Down`).
+    pub fn glyph(&self, action : Action, style : PromptStyle) -> String{
+        let m = match self.get(action){
+            Some(m) => m,
+            None    => return "-".to_string()
+        };
+        if style == PromptStyle::Keyboard {
+            let directions : Vec::<String> = CONTROLS.iter()
+                .filter(|c| c.action == action)
+                .filter_map(|c| m.bindings.iter().find(|b| b.source.style() == style && b.scale.signum() == c.scale.signum()))
+                .map(|b| b.source.glyph())
+                .collect();
+            if !directions.is_empty() {
+                return directions.join("/");
+            }
+        }
+        m.bindings.iter()
+            .find(|b| b.source.style() == style)
+            .map_or("-".to_string(), |b| b.source.glyph())
+    }
+
+    /// `template` with each `{Action}` replaced by the glyph of the action,
+    /// `{Confirm}: select` gives `Return: select` or `(A): select`.
+    pub fn format_prompt(&self, template : &str, style : PromptStyle) -> String{
+        let mut prompt = template.to_string();
+        for action in Action::ALL.iter() {
+            let placeholder = format!("{{{:?}}}", action);
+            if prompt.contains(&placeholder) {
+                prompt = prompt.replace(&placeholder, &self.glyph(*action, style));
+            }
+        }
+        prompt
+    }
+}
+
+/// What the devices hold right now, read through an `InputMap`.
+pub struct InputMapper{
+    held : HashSet::<(Device, Source)>,
+    axes : HashMap::<(Device, Source), f32>,
+}
+
+impl InputMapper{
+    pub fn new() -> Self{
+        InputMapper{
+            held : HashSet::<(Device, Source)>::new(),
+            axes : HashMap::<(Device, Source), f32>::new(),
+        }
+    }
+
+    pub fn press(&mut self, device : Device, source : Source){
+        self.held.insert((device, source));
+    }
+
+    pub fn release(&mut self, device : Device, source : Source){
+        self.held.remove(&(device, source));
+    }
+
+    pub fn move_axis(&mut self, device : Device, source : Source, value : f32){
+        self.axes.insert((device, source), value);
+    }
+
+    /// Lets go of everything a device held, when it is unplugged.
+    pub fn forget(&mut self, device : Device){
+        self.held.retain(|(d, _)| *d != device);
+        self.axes.retain(|(d, _), _| *d != device);
+    }
+
+    /// Value of the action between -1 and 1, read from the devices `accepts` lets through.
+    /// Every source adds up, so opposing keys held together cancel out and releasing one leaves the other.
+    /// A source counts once whatever the number of devices holding it,
+    /// an axis is read from the device pushing it furthest.
+    pub fn value(&self, map : &InputMap, action : Action, accepts : &dyn Fn(&Device) -> bool) -> f32{
+        let m = match map.get(action){
+            Some(m) => m,
+            None    => return 0.0
+        };
+        let total : f32 = m.bindings.iter().map(|b| {
+            match &b.source {
+                Source::Axis(_) => {
+                    let raw = self.axes.iter()
+                        .filter(|((device, source), _)| *source == b.source && accepts(device))
+                        .map(|(_, value)| *value)
+                        .fold(0.0, |a : f32, v : f32| if v.abs() > a.abs() { v } else { a });
+                    apply_deadzone(raw, m.deadzone) * b.scale
+                },
+                source if self.held.iter().any(|(device, held)| held == source && accepts(device)) => b.scale,
+                _ => 0.0
+            }
+        }).sum();
+        total.clamp(-1.0, 1.0)
+    }
+
+    pub fn is_held(&self, map : &InputMap, action : Action, accepts : &dyn Fn(&Device) -> bool) -> bool{
+        self.value(map, action, accepts) >= PRESS_THRESHOLD
+    }
+
+    /// The input of a player, read through their bindings from the devices `accepts` lets through.
+    pub fn input_state(&self, map : &InputMap, accepts : &dyn Fn(&Device) -> bool) -> InputState{
+        InputState{
+            xaxis   : self.value(map, Action::MoveX, accepts),
+            yaxis   : self.value(map, Action::MoveY, accepts),
+            fire    : self.is_held(map, Action::Fire, accepts),
+            confirm : self.is_held(map, Action::Confirm, accepts),
+            back    : self.is_held(map, Action::Back, accepts),
+        }
+    }
+}
+
+/// An axis with the dead zone cut out, still going from -1 to 1.
+pub fn apply_deadzone(value : f32, deadzone : f32) -> f32{
+    if value.abs() <= deadzone {
+        0.0
+    } else {
+        value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+    }
+}
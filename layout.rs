@@ -0,0 +1,143 @@
+use crate::unit::*;
+
+/// Where a layout sits on the screen, and how its items line up across the stack.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Anchor{
+    TopLeft,
+    Top,
+    TopRight,
+    Center,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Direction{
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LayoutItem{
+    /// A screen space actor, its top left corner is placed.
+    Actor(Id),
+    /// Empty room along the stack.
+    Space(f32),
+}
+
+/// A stack of screen space actors anchored to the screen.
+/// The world places them again before a draw that follows a change of a text
+/// or of the screen size, so everything that depends on it moves.
+pub struct Layout{
+    anchor    : Anchor,
+    direction : Direction,
+    /// Distance kept from the screen edges.
+    padding   : f32,
+    /// Distance between two items of the stack.
+    spacing   : f32,
+    items     : Vec::<LayoutItem>,
+}
+
+/// Start of a `length` long segment aligned in `room`, `padding` away from its ends.
+fn align(room : f32, length : f32, padding : f32, side : i32) -> f32{
+    match side {
+        -1 => padding,
+        0  => (room - length) / 2.0,
+        _  => room - padding - length
+    }
+}
+
+impl Layout{
+    pub fn new(anchor : Anchor, direction : Direction) -> Self{
+        Layout{
+            anchor,
+            direction,
+            padding : 0.0,
+            spacing : 0.0,
+            items   : Vec::<LayoutItem>::new(),
+        }
+    }
+
+    pub fn with_padding(mut self, padding : f32) -> Self{
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_spacing(mut self, spacing : f32) -> Self{
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn push(&mut self, item : LayoutItem){
+        self.items.push(item);
+    }
+
+    pub fn items(&self) -> &[LayoutItem]{
+        &self.items
+    }
+
+    /// Sides of the anchor, -1 for left or top, 0 for center, 1 for right or bottom.
+    fn sides(&self) -> (i32, i32){
+        match self.anchor {
+            Anchor::TopLeft     => (-1, -1),
+            Anchor::Top         => ( 0, -1),
+            Anchor::TopRight    => ( 1, -1),
+            Anchor::Center      => ( 0,  0),
+            Anchor::BottomLeft  => (-1,  1),
+            Anchor::Bottom      => ( 0,  1),
+            Anchor::BottomRight => ( 1,  1),
+        }
+    }
+
+    /// Top left corner of each item, given their sizes in the order of `items`.
+    /// Spaces are given their size by the layout, whatever is in `sizes` for them.
+    pub fn arrange(&self, sizes : &[Size], screen : &Size) -> Vec::<Position>{
+        let sizes : Vec::<Size> = self.items.iter().zip(sizes.iter()).map(|(item, size)| {
+            match (item, self.direction) {
+                (LayoutItem::Space(s), Direction::Horizontal) => Size{ x : *s, y : 0.0 },
+                (LayoutItem::Space(s), Direction::Vertical)   => Size{ x : 0.0, y : *s },
+                _ => *size
+            }
+        }).collect();
+
+        let gaps = self.spacing * (sizes.len().max(1) - 1) as f32;
+        let block = match self.direction {
+            Direction::Horizontal => Size{
+                x : sizes.iter().map(|s| s.x).sum::<f32>() + gaps,
+                y : sizes.iter().map(|s| s.y).fold(0.0, f32::max),
+            },
+            Direction::Vertical => Size{
+                x : sizes.iter().map(|s| s.x).fold(0.0, f32::max),
+                y : sizes.iter().map(|s| s.y).sum::<f32>() + gaps,
+            },
+        };
+
+        let (side_x, side_y) = self.sides();
+        let origin = Position{
+            x : align(screen.x, block.x, self.padding, side_x),
+            y : align(screen.y, block.y, self.padding, side_y),
+        };
+
+        let mut cursor = 0.0;
+        let mut positions = Vec::<Position>::new();
+        for size in &sizes {
+            let p = match self.direction {
+                Direction::Horizontal => Position{
+                    x : origin.x + cursor,
+                    y : origin.y + align(block.y, size.y, 0.0, side_y),
+                },
+                Direction::Vertical => Position{
+                    x : origin.x + align(block.x, size.x, 0.0, side_x),
+                    y : origin.y + cursor,
+                },
+            };
+            cursor += self.spacing + match self.direction {
+                Direction::Horizontal => size.x,
+                Direction::Vertical   => size.y,
+            };
+            positions.push(p);
+        }
+        positions
+    }
+}
@@ -1,46 +1,119 @@
 use crate::actors;
+use crate::broadphase;
+use crate::camera;
 use crate::cell;
 use crate::color;
 use crate::effect;
+use crate::flight;
+use crate::highscore;
+use crate::input;
+use crate::layout;
+use crate::menu;
 use crate::mesh_gen;
 use crate::render;
+use crate::settings;
+use crate::sound;
+use crate::synth;
 use crate::terrain;
 use crate::text;
 use crate::unit::*;
 use crate::GameState;
+use crate::InputState;
+use crate::MAX_PLAYERS;
 use crate::Systems;
 use ggez::graphics::Color;
 use ggez::Context;
 use rand::seq::SliceRandom;
 use rand::Rng;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::iter;
 use std::mem;
 
-fn random_rect(maxsize: f32, world_size: &Size) -> (Position, Size) {
-    let mut rng = rand::thread_rng();
-    let x = rng.gen_range(0.0, world_size.x) as f32;
-    let y = rng.gen_range(0.0, world_size.y) as f32;
-    let size = rng.gen_range(0.0, maxsize) as f32;
-    (Position { x: x, y: y }, Size { x: size, y: size })
+fn random_rect(maxsize: f32, world_size: &Size, rng: &mut GameRng) -> (Position, Size) {
+    let x = rng.gen_range(0.0, world_size.x);
+    let y = rng.gen_range(0.0, world_size.y);
+    let size = rng.gen_range(0.0, maxsize);
+    (Position { x, y }, Size { x: size, y: size })
 }
 
+/// Room between the texts of a page, in pixels.
+const PAGE_SPACING: f32 = 20.0;
+/// Room between the items of a long list, in pixels.
+const LIST_SPACING: f32 = 6.0;
+/// Room around and between the HUD texts, in pixels.
+const HUD_PADDING: f32 = 10.0;
+
+/// Trauma given to the camera when the ship hits something, and when a shot hits a rock.
+const HIT_TRAUMA: f32 = 0.6;
+const SHOT_TRAUMA: f32 = 0.25;
+
+/// Seconds the ship blinks after a hit, nothing can hit it meanwhile.
+const INVULNERABLE_TIME: f32 = 2.0;
+const BLINK_PERIOD: f32 = 0.1;
+
+/// Distance between two checkpoints of a level.
+const CHECKPOINT_SPACING: f32 = 1500.0;
+
+/// Units per second the camera, and the ship with it, move forward.
+const SCROLL_SPEED: f32 = 120.0;
+
+/// Cockpit of the ship of each player, and the HUD corner of their status.
+const PILOT_COLORS: [Color; MAX_PLAYERS] = [color::SKYBLUE, color::AMBER];
+const PILOT_CORNERS: [layout::Anchor; MAX_PLAYERS] = [layout::Anchor::TopLeft, layout::Anchor::TopRight];
+
 pub struct WorldBounds {
     pub min: Size,
     pub max: Size,
 }
 
 pub struct WorldChange {
-    pub score: u32,
+    /// Points won, with the ship that won them.
+    pub scores: Vec<(Id, u32)>,
     pub level: Option<Id>,
     pub dead_effect: bool,
+    /// Actor to add to the world after this update.
+    pub spawn: Option<actors::Actor>,
+    /// The actor the effect ran on leaves the world after this update.
+    pub despawn: bool,
+    /// Another actor leaving the world after this update.
+    pub despawn_other: Option<Id>,
+    /// The game should close.
+    pub quit: bool,
+    /// The ships hit.
+    pub hits: Vec<Id>,
+    /// Trauma added to the camera.
+    pub shake: f32,
+    /// The pause overlay should close.
+    pub resume: bool,
+    /// The paused level should be loaded again.
+    pub restart: bool,
+    /// The settings were edited, the app applies and saves them.
+    pub settings: bool,
+    /// A player and their control of `input::CONTROLS` waiting for a new key or button,
+    /// and the text model showing it.
+    pub rebind: Option<(usize, usize, Id)>,
+    /// Players of the new run starting with the scene of `level`.
+    pub players: Option<usize>,
 }
 
 impl WorldChange {
     pub fn default() -> WorldChange {
         WorldChange {
-            score: 0,
+            scores: Vec::new(),
             level: None,
             dead_effect: false,
+            spawn: None,
+            despawn: false,
+            despawn_other: None,
+            quit: false,
+            hits: Vec::new(),
+            shake: 0.0,
+            resume: false,
+            restart: false,
+            settings: false,
+            rebind: None,
+            players: None,
         }
     }
 }
@@ -50,9 +123,34 @@ type KeyedEffects = KeyedGroup<effect::Effect>;
 pub struct World {
     start_effects: KeyedEffects,
     tick_effects: KeyedEffects,
-    pub actors: Vec<super::actors::Actor>,
-    player_atr_id: Id,
+    /// Actors in drawing order, `actor_idx` maps their id to their position.
+    actors: Vec<super::actors::Actor>,
+    actor_idx: HashMap<Id, usize>,
+    /// Spawns and despawns requested during an update are applied at its end.
+    spawn_queue: Vec<super::actors::Actor>,
+    despawn_queue: HashSet<Id>,
+    /// The actors staying in place, filled once as the world starts, and the moving ones,
+    /// filled each step. Only moving actors look for contacts.
+    static_grid: broadphase::XGrid,
+    grid: broadphase::XGrid,
+    /// Screen space actors placed by layouts, and the screen size they were placed for.
+    layouts: Vec<layout::Layout>,
+    layout_screen: Option<Size>,
+    /// The ship of each player, in order. A ship leaves the world once its player has no life left.
+    players: Vec<Id>,
     camera_atr_id: Id,
+    camera: camera::Camera,
+    /// Where the players respawn, the start of the level then each checkpoint in order.
+    checkpoints: Vec<Position>,
+    /// The last checkpoint passed, and the score of each player then.
+    checkpoint: usize,
+    checkpoint_scores: Vec<i32>,
+    /// Pickups as the level started, the ones taken after the last checkpoint come back on respawn.
+    pickups: Vec<actors::Actor>,
+    /// Place of each actor in the drawing order as the level started, an actor coming back gets it again.
+    start_order: HashMap<Id, usize>,
+    /// Level loaded once no player has a life left.
+    lose_level: Option<Id>,
     //
     active: bool,
     pub name: String,
@@ -69,8 +167,22 @@ impl World {
             start_effects: KeyedEffects::new(),
             tick_effects: KeyedEffects::new(),
             actors: Vec::<super::actors::Actor>::new(),
-            player_atr_id: no_id(),
+            actor_idx: HashMap::<Id, usize>::new(),
+            spawn_queue: Vec::<super::actors::Actor>::new(),
+            despawn_queue: HashSet::<Id>::new(),
+            static_grid: broadphase::XGrid::new(),
+            grid: broadphase::XGrid::new(),
+            layouts: Vec::<layout::Layout>::new(),
+            layout_screen: None,
+            players: Vec::<Id>::new(),
             camera_atr_id: no_id(),
+            camera: camera::Camera::new(camera::CameraMode::Fixed, Size { x: 0.0, y: 0.0 }),
+            checkpoints: Vec::<Position>::new(),
+            checkpoint: 0,
+            checkpoint_scores: Vec::<i32>::new(),
+            pickups: Vec::<actors::Actor>::new(),
+            start_order: HashMap::<Id, usize>::new(),
+            lose_level: None,
             active: false,
             name: name,
             size: Size { x: 0.0, y: 0.0 },
@@ -78,11 +190,11 @@ impl World {
     }
 
     // pub fn start(&mut self, ctx: &Context, input : &super::InputState){
-    pub fn start(&mut self, ctx: &Context, state: &GameState, systems: &mut Systems) {
+    pub fn start(&mut self, state: &GameState, systems: &mut Systems) {
         self.active = true;
         //
         let wb = WorldBounds {
-            min: self.get_camera_actor().transform,
+            min: self.get_camera_actor().transform.position,
             max: self.size,
         };
         for a in &mut self.actors {
@@ -92,106 +204,341 @@ impl World {
         for a in &mut self.actors {
             for effs in self.start_effects.get_mut(&a.id) {
                 for e in effs {
-                    e.on_actor(a, ctx, state, &wb, systems);
+                    e.on_actor(a, 0.0, state, &wb, systems);
                 }
             }
         }
         self.start_effects.clear();
+
+        // placed by their start effects, the actors staying in place go in the grid for good.
+        self.static_grid.reset(self.size.x);
+        for a in &self.actors {
+            if a.moves() || !a.has_collision() {
+                continue;
+            }
+            let turns = self.tick_effects.get(&a.id).is_some_and(|effs| effs.iter().any(|e| e.turns()));
+            let aabb = if turns { a.reach_aabb() } else { a.aabb() };
+            self.static_grid.insert(a.id, aabb);
+        }
+
+        self.checkpoint = 0;
+        self.checkpoint_scores = state.pilots.iter().map(|p| p.score).collect();
+        self.pickups.clear();
+        self.start_order = self.actors.iter().enumerate().map(|(i, a)| (a.id, i)).collect();
+        for a in &self.actors {
+            if a.layer == actors::LAYER_PICKUP {
+                let mut pickup = a.clone();
+                pickup.on_tick = self.tick_effects.get(&a.id).cloned().unwrap_or_default();
+                self.pickups.push(pickup);
+            }
+        }
     }
 
     pub fn stop(&mut self) {
         self.active = false;
         self.actors.clear();
+        self.actor_idx.clear();
+        self.spawn_queue.clear();
+        self.despawn_queue.clear();
         self.tick_effects.clear();
+        self.layouts.clear();
+        self.layout_screen = None;
+    }
+
+    /// Stops the world and takes what it put in the systems out : the models of its actors,
+    /// its menus and prompts. For a world leaving while the one under it goes on.
+    pub fn release(&mut self, systems: &mut Systems) {
+        for a in &self.actors {
+            let drawable = a.get_drawable();
+            systems.renderer_source.meshmodels.remove(&drawable);
+            systems.renderer_source.textmodels.remove(&drawable);
+            systems.prompts.remove(&drawable);
+        }
+        for e in self.tick_effects.values().flatten() {
+            if let effect::Effect::Menu { menu_id } = e {
+                systems.menus.remove(menu_id);
+            }
+        }
+        self.stop();
+    }
+
+    pub fn actors(&self) -> &[actors::Actor] {
+        &self.actors
+    }
+
+    /// The text model drawn by the layout item, if any.
+    fn layout_text<'a>(&self, item: &layout::LayoutItem, systems: &'a Systems) -> Option<&'a render::TextModel> {
+        match item {
+            layout::LayoutItem::Actor(id) => {
+                let drawable = self.get_actor(id)?.get_drawable();
+                systems.renderer_source.get_text_model(&drawable)
+            }
+            layout::LayoutItem::Space(_) => None,
+        }
+    }
+
+    /// Places the actors of the layouts on a `screen` sized screen, measuring their texts.
+    /// Nothing moves until the screen size or one of the texts changes.
+    pub fn relayout(&mut self, systems: &Systems, screen: &Size, ctx: &mut Context) {
+        let changed = self.layouts.iter().any(|l| {
+            l.items().iter().any(|item| self.layout_text(item, systems).is_some_and(|tm| tm.dirty))
+        });
+        if self.layout_screen == Some(*screen) && !changed {
+            return;
+        }
+        self.layout_screen = Some(*screen);
+
+        for l in &self.layouts {
+            let mut sizes = Vec::<Size>::new();
+            for item in l.items() {
+                let mut size = Size { x: 0.0, y: 0.0 };
+                if let Some(tm) = self.layout_text(item, systems) {
+                    let (w, h) = tm.get_screen_size(&systems.renderer, ctx);
+                    size = Size { x: w as f32, y: h as f32 };
+                }
+                sizes.push(size);
+            }
+
+            for (item, p) in l.items().iter().zip(l.arrange(&sizes, screen)) {
+                if let layout::LayoutItem::Actor(id) = item {
+                    if let Some(idx) = self.actor_idx.get(id) {
+                        self.actors[*idx].transform.position = p;
+                    }
+                }
+            }
+        }
+    }
+
+    fn insert_actor(&mut self, a: actors::Actor) -> Id {
+        let id = a.id;
+        self.actor_idx.insert(id, self.actors.len());
+        self.actors.push(a);
+        id
     }
 
     pub fn get_camera_actor(&self) -> &actors::Actor {
         self.get_actor(&self.camera_atr_id).unwrap()
     }
 
-    fn get_player_actor(&self) -> &actors::Actor {
-        self.get_actor(&self.player_atr_id).unwrap()
+    /// Where the renderer looks from, the camera actor position moved by the shake.
+    pub fn camera_draw_position(&self) -> Position {
+        opposite_pos(&self.camera.shaken_origin())
     }
 
-    pub fn get_actor(&self, id: &Id) -> Option<&actors::Actor> {
-        for a in &self.actors {
-            if a.id == *id {
-                return Some(a);
-            }
+    /// Where the ships still in the world are.
+    fn ship_positions(&self) -> Vec<Position> {
+        self.players
+            .iter()
+            .filter_map(|id| self.get_actor(id))
+            .map(|a| a.transform.position)
+            .collect()
+    }
+
+    /// Moves the camera and the camera actor along, following the middle of the ships.
+    fn update_camera(&mut self, dt: f32) {
+        let ships = self.ship_positions();
+        let target = if ships.is_empty() {
+            None
+        } else {
+            let count = ships.len() as f32;
+            Some(Position {
+                x: ships.iter().map(|p| p.x).sum::<f32>() / count,
+                y: ships.iter().map(|p| p.y).sum::<f32>() / count,
+            })
+        };
+        self.camera.update(dt, target, &self.size);
+        let camera_id = self.camera_atr_id;
+        let position = opposite_pos(&self.camera.origin());
+        if let Some(camera) = self.get_mut_actor(&camera_id) {
+            camera.transform.position = position;
         }
-        None
+    }
+
+    pub fn has_player(&self) -> bool {
+        !self.players.is_empty()
+    }
+
+    /// Whether back does something here : pausing the players, leaving a menu or editing a name.
+    pub fn reads_back(&self) -> bool {
+        self.has_player()
+            || self.tick_effects.values().flatten().any(|e| {
+                matches!(e, effect::Effect::Menu { .. } | effect::Effect::EnterName { .. })
+            })
+    }
+
+    /// The player flying the ship `id`.
+    fn pilot_of(&self, id: &Id) -> Option<usize> {
+        self.players.iter().position(|ship| ship == id)
+    }
+
+    pub fn get_actor(&self, id: &Id) -> Option<&actors::Actor> {
+        self.actor_idx.get(id).map(|idx| &self.actors[*idx])
     }
 
     fn get_mut_actor(&mut self, id: &Id) -> Option<&mut actors::Actor> {
-        for a in &mut self.actors {
-            if a.id == *id {
-                return Some(a);
+        match self.actor_idx.get(id) {
+            Some(idx) => Some(&mut self.actors[*idx]),
+            None => None,
+        }
+    }
+
+    /// Applies the despawns then the spawns requested during the update.
+    /// Spawned actors get their `on_tick` effects as tick effects.
+    fn flush_queues(&mut self) {
+        if !self.despawn_queue.is_empty() {
+            let despawned = mem::take(&mut self.despawn_queue);
+            self.actors.retain(|a| !despawned.contains(&a.id));
+            for id in &despawned {
+                self.tick_effects.remove(id);
+                self.start_effects.remove(id);
+                self.static_grid.remove(id);
+            }
+            self.actor_idx.clear();
+            for (i, a) in self.actors.iter().enumerate() {
+                self.actor_idx.insert(a.id, i);
+            }
+        }
+
+        for mut a in mem::take(&mut self.spawn_queue) {
+            a.start();
+            let effs = mem::take(&mut a.on_tick);
+            if !effs.is_empty() {
+                self.tick_effects.insert(a.id, effs);
+            }
+            if self.start_order.contains_key(&a.id) {
+                self.reinsert_actor(a);
+            } else {
+                self.insert_actor(a);
             }
         }
-        None
     }
 
-    fn process_collisions(&mut self) {
-        if self.player_atr_id == no_id() {
-            return;
+    /// Puts an actor of the level back where it was in the drawing order,
+    /// before the actors that followed it and the ones spawned since.
+    fn reinsert_actor(&mut self, a: actors::Actor) {
+        let rank = self.start_order[&a.id];
+        let idx = self
+            .actors
+            .iter()
+            .position(|other| self.start_order.get(&other.id).is_none_or(|r| *r > rank))
+            .unwrap_or(self.actors.len());
+        self.actors.insert(idx, a);
+        for (i, a) in self.actors.iter().enumerate().skip(idx) {
+            self.actor_idx.insert(a.id, i);
         }
+    }
 
-        let player_actor = self.get_player_actor();
-        let size1 = player_actor.collision.get_size();
-        let pos1 = Position {
-            x: player_actor.transform.x + size1.x / 2.0,
-            y: player_actor.transform.y + size1.y / 2.0,
-        };
-        let collision1 = player_actor.collision.clone();
+    /// Fills the broadphase grid with the moving actors that can collide this frame.
+    fn rebuild_grid(&mut self) {
+        self.grid.reset(self.size.x);
+        for a in &self.actors {
+            if a.moves() && a.has_collision() {
+                self.grid.insert(a.id, a.aabb());
+            }
+        }
+    }
 
-        for a in &mut self.actors {
-            if !a.has_collision() {
+    /// Every moving actor is tested against the actors the grids put near it.
+    /// Each side of a contact reacting to the other gets its collision effects, bound to the other actor.
+    fn process_collisions(&mut self) {
+        self.rebuild_grid();
+        let mut nearby = Vec::<Id>::new();
+        let mut contacts = Vec::<(usize, usize)>::new();
+
+        for (i, a) in self.actors.iter().enumerate() {
+            if !a.moves() || !a.has_collision() {
                 continue;
             }
+            let aabb = a.aabb();
+            for (grid, moving) in [(&self.static_grid, false), (&self.grid, true)] {
+                grid.query(&aabb, actors::COLLISION_PREDICTION, &mut nearby);
+                for id in &nearby {
+                    let j = match self.actor_idx.get(id) {
+                        Some(j) => *j,
+                        None => continue,
+                    };
+                    // two moving actors find each other, the first one takes the contact.
+                    if j == i || (moving && j < i) {
+                        continue;
+                    }
+                    let other = &self.actors[j];
+                    if !other.has_collision() || !(a.reacts_to(other) || other.reacts_to(a)) {
+                        continue;
+                    }
+                    if super::actors::collides(&a.collision_transform(), a.collision(), &other.collision_transform(), other.collision()) {
+                        contacts.push((i, j));
+                    }
+                }
+            }
+        }
 
-            let size2 = a.collision.get_size();
-            let pos2 = Position {
-                x: a.transform.x + size2.x / 2.0,
-                y: a.transform.y + size2.y / 2.0,
-            };
-
-            if super::actors::collides(&pos1, &collision1, &pos2, &a.collision) {
-                self.tick_effects.insert(a.id, a.on_collision.clone());
+        for (i, j) in contacts {
+            for (one, other) in [(i, j), (j, i)] {
+                let (a, b) = (&self.actors[one], &self.actors[other]);
+                if !a.reacts_to(b) {
+                    continue;
+                }
+                let effs = a.collision_effects(b);
+                if !effs.is_empty() {
+                    self.tick_effects.entry(a.id).or_default().extend(effs);
+                }
             }
         }
     }
 
+    /// Advances the world by `dt` seconds.
     pub fn update(
         &mut self,
-        ctx: &Context,
+        dt: f32,
         state: &GameState,
         systems: &mut Systems,
     ) -> WorldChange {
         let mut default_wc = WorldChange::default();
 
-        if state.paused {
-            return default_wc;
-        }
-
         self.process_collisions();
 
-        let wb = WorldBounds {
-            min: opposite_pos(&self.get_camera_actor().transform),
-            max: self.size,
+        let min = opposite_pos(&self.get_camera_actor().transform.position);
+        // ships sharing the view can not leave it, or the camera would lose one.
+        let max = if self.players.len() > 1 {
+            Size {
+                x: (min.x + self.camera.view().x).min(self.size.x),
+                y: self.size.y,
+            }
+        } else {
+            self.size
         };
+        let wb = WorldBounds { min, max };
         for a in &mut self.actors {
             let mut eff_to_remove = Vec::<usize>::new();
             for effs in self.tick_effects.get_mut(&a.id) {
                 for (i, e) in effs.iter_mut().enumerate() {
-                    if let Some(wc) = e.on_actor(a, ctx, state, &wb, systems) {
+                    if let Some(wc) = e.on_actor(a, dt, state, &wb, systems) {
+                        if wc.quit || wc.resume || wc.restart {
+                            return wc;
+                        }
                         if let Some(_) = wc.level {
                             return wc;
                         } else {
-                            default_wc.score += wc.score;
+                            default_wc.scores.extend(wc.scores);
+                        }
+                        default_wc.hits.extend(wc.hits);
+                        default_wc.settings |= wc.settings;
+                        if wc.rebind.is_some() {
+                            default_wc.rebind = wc.rebind;
                         }
+                        default_wc.shake += wc.shake;
                         if wc.dead_effect {
                             eff_to_remove.push(i);
                         }
+                        if let Some(new_actor) = wc.spawn {
+                            self.spawn_queue.push(new_actor);
+                        }
+                        if wc.despawn {
+                            self.despawn_queue.insert(a.id);
+                        }
+                        if let Some(other_id) = wc.despawn_other {
+                            self.despawn_queue.insert(other_id);
+                        }
                     }
                 }
 
@@ -200,8 +547,154 @@ impl World {
                 }
             }
         }
+
+        self.camera.add_trauma(default_wc.shake);
+        self.update_camera(dt);
+        self.flush_queues();
         default_wc
     }
+
+    /// Headless stepping : feeds `inputs`, one per player, to the game state,
+    /// advances the world by `dt` seconds and accumulates the scores.
+    /// Worlds without a player only read the first input.
+    /// Nothing here needs a window or an audio device.
+    pub fn step(
+        &mut self,
+        dt: f32,
+        inputs: &[InputState],
+        state: &mut GameState,
+        systems: &mut Systems,
+    ) -> WorldChange {
+        state.input = inputs.first().copied().unwrap_or_default();
+        if self.has_player() {
+            for (pilot, input) in state.pilots.iter_mut().zip(inputs) {
+                pilot.input = *input;
+            }
+        }
+        let mut wc = self.update(dt, state, systems);
+        for (ship, points) in &wc.scores {
+            if let Some(pilot) = self.pilot_of(ship) {
+                state.pilots[pilot].score += *points as i32;
+            }
+        }
+        self.pass_checkpoints(state);
+        let mut hit = Vec::<usize>::new();
+        for ship in &wc.hits {
+            match self.pilot_of(ship) {
+                // a ship touching several things is hit once.
+                Some(pilot) if wc.level.is_none() && !hit.contains(&pilot) => {
+                    hit.push(pilot);
+                    wc.level = self.hit_player(state, pilot, systems);
+                }
+                _ => (),
+            }
+        }
+        wc
+    }
+
+    /// A checkpoint is passed once a ship reaches it.
+    fn pass_checkpoints(&mut self, state: &GameState) {
+        let ship_x = match self.ship_positions().iter().map(|p| p.x).reduce(f32::max) {
+            Some(x) => x,
+            None => return,
+        };
+        while self.checkpoint + 1 < self.checkpoints.len()
+            && ship_x >= self.checkpoints[self.checkpoint + 1].x
+        {
+            self.checkpoint += 1;
+            self.checkpoint_scores = state.pilots.iter().map(|p| p.score).collect();
+        }
+    }
+
+    /// Puts the ship of `pilot` back in the game. Next to another ship still flying if any,
+    /// the view stays where it is then. Otherwise back to the last checkpoint : the ship and
+    /// the camera are placed as they were at the start, relative to it, and what the player
+    /// scored since is taken back.
+    fn respawn(&mut self, state: &mut GameState, pilot: usize) {
+        let ship_id = self.players[pilot];
+        let partner = self
+            .players
+            .iter()
+            .filter(|id| **id != ship_id)
+            .find_map(|id| self.get_actor(id))
+            .map(|a| a.transform.position);
+        let p = match (partner, self.checkpoints.get(self.checkpoint)) {
+            (Some(p), _) => p,
+            (None, Some(p)) => *p,
+            (None, None) => return,
+        };
+        if let Some(ship) = self.get_mut_actor(&ship_id) {
+            ship.transform.position = p;
+            ship.transform.rotation = 0.0;
+        }
+        for e in self.tick_effects.entry(ship_id).or_default() {
+            if let effect::Effect::ProcessInput { velocity, .. } = e {
+                *velocity = Vector2::zeros();
+            }
+        }
+        if partner.is_some() {
+            return;
+        }
+
+        let start_x = self.checkpoints[0].x;
+        let camera_y = self.camera.origin().y;
+        self.camera.place(Position { x: p.x - start_x, y: camera_y });
+        self.update_camera(0.0);
+
+        if let Some(score) = self.checkpoint_scores.get(pilot) {
+            state.pilots[pilot].score = *score;
+        }
+        for pickup in &self.pickups {
+            if pickup.transform.position.x >= p.x && !self.actor_idx.contains_key(&pickup.id) {
+                self.spawn_queue.push(pickup.clone());
+            }
+        }
+        self.flush_queues();
+    }
+
+    /// The shield of the player takes the hit if it can, with a crash, otherwise they lose a life
+    /// and respawn, with the death sound alone.
+    /// A player without lives is out, their ship leaves the world.
+    /// Returns the level to load once every player is out.
+    fn hit_player(&mut self, state: &mut GameState, pilot: usize, systems: &mut Systems) -> Option<Id> {
+        let ship_id = self.players[pilot];
+        let p = &mut state.pilots[pilot];
+        let sound = if p.shield > 0 { sound::CRASH_SOUND } else { sound::DEATH_SOUND };
+        if let Some(group) = systems.mixer.group(sound) {
+            systems.mixer.play(group);
+        }
+        if p.shield > 0 {
+            p.shield -= 1;
+        } else {
+            p.lives -= 1;
+            if p.lives <= 0 {
+                if state.pilots.iter().all(|p| p.lives <= 0) {
+                    return self.lose_level;
+                }
+                self.despawn_queue.insert(ship_id);
+                self.flush_queues();
+                return None;
+            }
+            self.respawn(state, pilot);
+        }
+        // out of reach right away, the collisions of the next step run before the blink.
+        // Pickups and triggers still see the ship.
+        let mask = match self.get_mut_actor(&ship_id) {
+            Some(ship) => {
+                let mask = ship.mask;
+                ship.mask &= !actors::LAYER_HAZARDS;
+                mask
+            }
+            None => return None,
+        };
+        let blink = effect::Effect::Invulnerable {
+            time: INVULNERABLE_TIME,
+            blink: BLINK_PERIOD,
+            mask,
+        };
+        self.tick_effects.entry(ship_id).or_default().push(blink);
+        None
+    }
 }
 
 struct WorldBuilder {
@@ -253,19 +746,15 @@ impl WorldBuilder {
     // }
 
     fn add_to_world(&mut self, a: actors::Actor) -> Id {
-        let atr_id = a.id.clone();
-        self.w.actors.push(a);
-        atr_id
+        self.w.insert_actor(a)
     }
 
-    fn add_rect_type(&mut self, mut a: super::actors::Actor, max_size: f32, color: Color) -> Id {
-        let (pos, size) = random_rect(max_size, &self.w.size);
+    fn add_rect_type(&mut self, mut a: super::actors::Actor, max_size: f32, color: Color, rng: &mut GameRng) -> Id {
+        let (pos, size) = random_rect(max_size, &self.w.size, rng);
 
-        a.transform = pos;
+        a.transform.position = pos;
         // self.add_rect_to_actor(&mut a, size, color);
-        let atr_id = a.id.clone();
-        self.w.actors.push(a);
-        atr_id
+        self.w.insert_actor(a)
     }
 
     //
@@ -274,20 +763,19 @@ impl WorldBuilder {
         let size = Size { x: 10.0, y: 10.0 };
         let mut a = actors::ActorType::Player.make();
         // self.add_rect_to_actor(&mut a, size, super::color::RED);
-        self.w.player_atr_id = a.id.clone();
-        self.w.actors.push(a);
-        self.w.player_atr_id.clone()
+        self.w.players.push(a.id);
+        self.w.insert_actor(a)
     }
 
     fn add_camera(&mut self) -> Id {
         let mut a = actors::ActorType::Camera.make();        
-        a.transform = Position {
+        a.transform.position = Position {
             x: 0 as f32,
             y: 0 as f32,
         };
 
         self.w.camera_atr_id = a.id.clone();
-        self.w.actors.push(a);
+        self.w.insert_actor(a);
         self.w.camera_atr_id.clone()
     }
 
@@ -307,9 +795,21 @@ impl WorldBuilder {
         }
     }
 
-    fn add_antagonist(&mut self, max_size: f32) -> Id {
+    /// One collision actor per segment of `pts`, so the broadphase
+    /// only hands the nearby part of a long wall to the narrow phase.
+    fn add_terrain_segments(&mut self, pts: &[Position]) {
+        for segment in pts.windows(2) {
+            let mut a = actors::ActorType::Background.make();
+            a.set_collision(actors::mk_polycol(&segment.to_vec()));
+            a.ticking = true;
+            self.add_to_world(a);
+        }
+    }
+
+    fn add_antagonist(&mut self, max_size: f32, rng: &mut GameRng) -> Id {
         let a = actors::ActorType::Foreground.make();
-        return self.add_rect_type(a, max_size, color::random_foreground_color());
+        let color = color::random_foreground_color(rng);
+        return self.add_rect_type(a, max_size, color, rng);
     }
 
     fn add_text(&mut self, text: String, fontstyle: super::text::FontStyle, position: &Position, centered: bool, systems : &mut Systems) -> Id {
@@ -323,10 +823,91 @@ impl WorldBuilder {
             ));
 
         a.add_drawable(id);        
-        a.transform = position.clone();
+        a.transform.position = *position;
         self.add_to_world(a)
     }
 
+    /// Returns the index to add items to the layout with.
+    fn add_layout(&mut self, l: layout::Layout) -> usize {
+        self.w.layouts.push(l);
+        self.w.layouts.len() - 1
+    }
+
+    /// A text placed by the layout `layout_idx`.
+    fn add_layout_text(&mut self, layout_idx: usize, text: String, fontstyle: text::FontStyle, systems: &mut Systems) -> Id {
+        let id = self.add_text(text, fontstyle, &Origin, false, systems);
+        self.w.layouts[layout_idx].push(layout::LayoutItem::Actor(id));
+        id
+    }
+
+    /// The centered column the title pages stack their texts in.
+    fn add_page_layout(&mut self) -> usize {
+        self.add_layout(layout::Layout::new(layout::Anchor::Center, layout::Direction::Vertical).with_spacing(PAGE_SPACING))
+    }
+
+    /// A text showing `template` with the inputs of the actions named in it,
+    /// kept up to date with the device played last.
+    fn add_layout_prompt(&mut self, layout_idx: usize, template: String, fontstyle: text::FontStyle, systems: &mut Systems) -> Id {
+        let prompt = systems.settings.input.format_prompt(&template, systems.prompt_style);
+        let id = self.add_layout_text(layout_idx, prompt, fontstyle, systems);
+        let drawable = self.get_actor(&id).unwrap().get_drawable();
+        systems.prompts.insert(drawable, template);
+        self.add_effect_to_actor(&id, effect::Effect::UpdatePrompt, false);
+        id
+    }
+
+    /// A page too long for `add_page_layout` : the title at the top, the hints at the bottom
+    /// and the menu tightly stacked between them.
+    fn add_list_page(&mut self, level: &Level, items: &[(String, menu::MenuAction)], back: Option<usize>, systems: &mut Systems) {
+        let margin = 2.0 * PAGE_SPACING;
+        let header = self.add_layout(layout::Layout::new(layout::Anchor::Top, layout::Direction::Vertical).with_padding(margin));
+        self.add_layout_text(header, level.params.text(0), text::title_style(), systems);
+
+        let list = self.add_layout(layout::Layout::new(layout::Anchor::Center, layout::Direction::Vertical).with_spacing(LIST_SPACING));
+        self.add_menu(list, items, back, systems);
+
+        let footer = self.add_layout(
+            layout::Layout::new(layout::Anchor::Bottom, layout::Direction::Vertical)
+                .with_padding(margin)
+                .with_spacing(PAGE_SPACING),
+        );
+        for line in level.params.texts.iter().skip(1) {
+            self.add_layout_prompt(footer, line.clone(), text::tuto_style(), systems);
+        }
+    }
+
+    fn add_layout_space(&mut self, layout_idx: usize, size: f32) {
+        self.w.layouts[layout_idx].push(layout::LayoutItem::Space(size));
+    }
+
+    /// One text actor per item in the layout `layout_idx`, and the actor running the menu.
+    /// Back picks the item `back`, if any.
+    fn add_menu(
+        &mut self,
+        layout_idx: usize,
+        items: &[(String, menu::MenuAction)],
+        back: Option<usize>,
+        systems: &mut Systems,
+    ) -> Id {
+        let mut m = menu::Menu::new();
+        if let Some(idx) = back {
+            m.set_back(idx);
+        }
+        for (i, (label, action)) in items.iter().enumerate() {
+            let id = self.add_layout_text(layout_idx, label.clone(), m.item_style(i), systems);
+            let drawable = self.get_actor(&id).unwrap().get_drawable();
+            m.push(drawable, *action);
+        }
+
+        let mut a = actors::ActorType::UI.make();
+        a.drawctx = actors::DrawContext::ScreenSpace;
+        let id = self.add_to_world(a);
+        let menu_id = get_id();
+        systems.menus.insert(menu_id, m);
+        self.add_effect_to_actor(&id, effect::Effect::Menu { menu_id }, false);
+        id
+    }
+
     // fn add_end_rects(&mut self, exit_size: f32) -> [Id; 3] {
     //     let lose_rect_height = (self.w.size.y - exit_size) / 2.0;
 
@@ -338,7 +919,7 @@ impl WorldBuilder {
     //         .enumerate()
     //     {
     //         let mut a = actors::ActorType::Foreground.make();
-    //         a.transform = Position {
+    //         a.transform.position = Position {
     //             x: self.w.size.x as f32,
     //             y: yy as f32,
     //         };
@@ -362,7 +943,7 @@ impl WorldBuilder {
     //     a.drawable = systems.renderer.add_dynamic_poly(&pts11.clone(), color::BLACK);
     //     a.collision = actors::mk_polycol(&pts);
     //     a.on_collision.push( eff_on_col );
-    //     a.transform = c2.get_point(0.0, 0.0);
+    //     a.transform.position = c2.get_point(0.0, 0.0);
     //     wb.debug_pos(&a.transform);
     //     wb.add_to_world(a);
     // }
@@ -402,7 +983,7 @@ impl WorldBuilder {
         self.debug_polyline(&pts, &pt_to_pos(&origin));
     }
 
-    pub fn add_debug_actor(&mut self, systems: &mut Systems, ctx: &mut Context) -> Id {
+    pub fn add_debug_actor(&mut self, systems: &mut Systems) -> Id {
         if self.debug_mm.polygons.len() > 0 {
             let mut a = actors::ActorType::Background.make();
             let debug_mm = mem::replace(&mut self.debug_mm, render::MeshModel::new());            
@@ -417,13 +998,62 @@ impl WorldBuilder {
 
     // BUILD
 
-    fn build(mut self, systems: &mut Systems, ctx: &mut Context) -> World {
-        self.add_debug_actor(systems, ctx);
+    fn build(mut self, systems: &mut Systems) -> World {
+        self.add_debug_actor(systems);
         self.w
     }
 }
 
-type LevelLoader = fn(&Level, &mut GameState, &mut Systems, &mut Context) -> World;
+/// Loaders get no context when running headless.
+pub type LevelLoader = fn(&Level, &mut GameState, &mut Systems, Option<&mut Context>) -> World;
+
+/// Menu item action closing the game.
+pub const QUIT_ACTION: &str = "quit";
+/// Menu item action closing the pause overlay.
+pub const RESUME_ACTION: &str = "resume";
+/// Menu item action loading the paused level again.
+pub const RESTART_ACTION: &str = "restart";
+/// Menu item actions handled by the game, the others name a transition.
+pub const BUILTIN_ACTIONS: &[&str] = &[QUIT_ACTION, RESUME_ACTION, RESTART_ACTION];
+/// The transition of the menu items leading back to the previous page.
+pub const BACK_TRANSITION: &str = "back";
+
+/// The scene shown over a paused level, if the graph has one.
+pub const PAUSE_LEVEL: &str = "pause";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct MenuItemDesc {
+    pub label: String,
+    pub action: String,
+    /// Set on the items starting a new run, for that many players.
+    #[serde(default)]
+    pub players: Option<usize>,
+}
+
+/// Tunables handed to a loader, set per level in the level graph file.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoaderParams {
+    /// Seconds before the automatic transition.
+    pub duration: f32,
+    pub texts: Vec<String>,
+    pub items: Vec<MenuItemDesc>,
+    /// Flight model of the level, the one of the settings when unset.
+    pub flight: Option<flight::FlightModel>,
+    /// How the view moves, scrolling at the default speed when unset.
+    pub camera: Option<camera::CameraMode>,
+    /// The player a scene is about, 0 for the first.
+    pub player: usize,
+    /// Looping music of the scene, a synthesized tune or a file of the resources. It cross-fades
+    /// from the music playing, the music goes on when unset and fades out when empty.
+    pub music: Option<String>,
+}
+
+impl LoaderParams {
+    fn text(&self, idx: usize) -> String {
+        self.texts.get(idx).cloned().unwrap_or_default()
+    }
+}
 
 #[derive(Clone)]
 pub struct Level {
@@ -431,6 +1061,7 @@ pub struct Level {
     name: String,
     transitions: HashMap<String, Id>,
     pub loader: LevelLoader,
+    pub params: LoaderParams,
 }
 
 impl Level {
@@ -440,6 +1071,7 @@ impl Level {
             name: name,
             transitions: HashMap::<String, Id>::new(),
             loader: emptyload,
+            params: LoaderParams::default(),
         }
     }
 
@@ -448,167 +1080,347 @@ impl Level {
             .insert(transition_name.clone(), level.id.clone());
     }
 
-    pub fn load(&self, state: &mut GameState, systems: &mut Systems, ctx: &mut Context) -> World {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn load(&self, state: &mut GameState, systems: &mut Systems, ctx: Option<&mut Context>) -> World {
         return (self.loader)(self, state, systems, ctx);
     }
 
+    pub fn get_transition(&self, transition_name: &str) -> Id {
+        *self.transitions.get(transition_name).unwrap()
+    }
+
+    /// The level a transition leads to, if this level has it.
+    pub fn find_transition(&self, transition_name: &str) -> Option<Id> {
+        self.transitions.get(transition_name).copied()
+    }
+
     pub fn get_transition_effect(&self, transition_name: String, duration: f32) -> effect::Effect {
-        let next_id = self.transitions.get(&transition_name).unwrap();
         effect::Effect::AutoNextScene {
             duration: duration,
             cur_scene_idx: self.id.clone(),
-            next_scene_idx: next_id.clone(),
+            next_scene_idx: self.get_transition(&transition_name),
         }
     }
 }
 
-fn emptyload(
+pub fn emptyload(
     level: &Level,
     state: &mut GameState,
     systems: &mut Systems,
-    ctx: &mut Context,
+    _ctx: Option<&mut Context>,
 ) -> World {
     let wb = WorldBuilder::new(level.name.clone());
-    wb.build(systems, ctx)
+    wb.build(systems)
 }
 
 pub fn introload(
     level: &Level,
-    state: &mut GameState,
+    _state: &mut GameState,
     systems: &mut Systems,
-    ctx: &mut Context,
+    _ctx: Option<&mut Context>,
 ) -> World {
     let mut wb = WorldBuilder::new(level.name.clone());
 
-    let center = Position {
-        x: state.screen.x / 2.0,
-        y: state.screen.y / 2.0,
-    };
-
-    let id = wb.add_text("Pulsar 3".to_string(), text::title_style(), &center, false, systems );
+    let page = wb.add_page_layout();
+    let id = wb.add_layout_text(page, level.params.text(0), text::title_style(), systems);
     wb.add_effect_to_actor(
         &id,
-        level.get_transition_effect("next".to_string(), 0.0),
+        level.get_transition_effect("next".to_string(), level.params.duration),
         false,
     );    
     wb.add_default_camera();
-    wb.build(systems, ctx)
+    wb.build(systems)
 }
 
 pub fn tutoload(
     level: &Level,
     state: &mut GameState,
     systems: &mut Systems,
-    ctx: &mut Context,
+    _ctx: Option<&mut Context>,
 ) -> World {
     let mut wb = WorldBuilder::new(level.name.clone());
 
-    let y_step = state.screen.y / 5.0;
-    let center = Position {
-        x: state.screen.x / 2.0,
-        y: y_step * 2.0,
-    };
-
+    let page = wb.add_page_layout();
     let tuto_text = format!("Level {}", state.level);
-    let id = wb.add_text(tuto_text, text::title_style(), &center, true, systems);    
+    wb.add_layout_text(page, tuto_text, text::title_style(), systems);
+    wb.add_layout_space(page, PAGE_SPACING);
 
-    let center = Position {
-        x: state.screen.x / 2.0,
-        y: y_step * 3.0,
-    };
-    let tuto_text = "Don't crash on the cavern walls...".to_string();
-    let id = wb.add_text(tuto_text, text::tuto_style(), &center, true, systems);
+    for i in 0..2 {
+        let id = wb.add_layout_text(page, level.params.text(i), text::tuto_style(), systems);
+        wb.add_effect_to_actor(
+            &id,
+            level.get_transition_effect("next".to_string(), level.params.duration),
+            false,
+        );
+    }
+    wb.add_default_camera();
+    wb.build(systems)
+}
+
+pub fn gameoverload(
+    level: &Level,
+    state: &mut GameState,
+    systems: &mut Systems,
+    _ctx: Option<&mut Context>,
+) -> World {
+    state.last_run = Some(highscore::FinishedRun {
+        score: state.best_score(),
+        level: state.level,
+        seed: state.seed,
+    });
+    state.reset_run();
+
+    let mut wb = WorldBuilder::new(level.name.clone());
+
+    let page = wb.add_page_layout();
+    let id = wb.add_layout_text(page, level.params.text(0), text::title_style(), systems);
     wb.add_effect_to_actor(
         &id,
-        level.get_transition_effect("next".to_string(), 3.0),
+        level.get_transition_effect("next".to_string(), level.params.duration),
         false,
     );
     
 
-    let center = Position {
-        x: state.screen.x / 2.0,
-        y: y_step * 4.0,
-    };
-    let tuto_text = "and catch the yellow blocks.".to_string();
-    let id = wb.add_text(tuto_text, text::tuto_style(), &center, true, systems);
-    wb.add_effect_to_actor(
-        &id,
-        level.get_transition_effect("next".to_string(), 3.0),
-        false,
-    );    
     wb.add_default_camera();
-    wb.build(systems, ctx)
+    wb.build(systems)
 }
 
-pub fn gameoverload(
+/// A title, some lines of text and a menu, for the main menu and the pages it leads to.
+pub fn menuload(
     level: &Level,
-    state: &mut GameState,
+    _state: &mut GameState,
     systems: &mut Systems,
-    ctx: &mut Context,
+    _ctx: Option<&mut Context>,
 ) -> World {
-    state.level = 0;
-    state.score = 0;
+    let mut wb = WorldBuilder::new(level.name.clone());
+
+    let page = wb.add_page_layout();
+    wb.add_layout_text(page, level.params.text(0), text::title_style(), systems);
+    wb.add_layout_space(page, PAGE_SPACING);
+    for line in level.params.texts.iter().skip(1) {
+        wb.add_layout_prompt(page, line.clone(), text::tuto_style(), systems);
+    }
+    wb.add_layout_space(page, PAGE_SPACING);
+
+    let items = menu_items(level);
+    wb.add_menu(page, &items, back_item(level, 0), systems);
+
+    wb.add_default_camera();
+    wb.build(systems)
+}
 
+/// The menu items of the level params, with their actions.
+fn menu_items(level: &Level) -> Vec<(String, menu::MenuAction)> {
+    level
+        .params
+        .items
+        .iter()
+        .map(|item| {
+            let action = match (item.action.as_str(), item.players) {
+                (QUIT_ACTION, _) => menu::MenuAction::Quit,
+                (RESUME_ACTION, _) => menu::MenuAction::Resume,
+                (RESTART_ACTION, _) => menu::MenuAction::Restart,
+                (transition, Some(players)) => menu::MenuAction::NewRun {
+                    next_scene_idx: level.get_transition(transition),
+                    players,
+                },
+                (transition, None) => menu::MenuAction::Goto(level.get_transition(transition)),
+            };
+            (item.label.clone(), action)
+        })
+        .collect()
+}
+
+/// The item of the params back picks, `offset` items after the start of the menu :
+/// the one going back, closing the pause or leaving the game.
+fn back_item(level: &Level, offset: usize) -> Option<usize> {
+    level
+        .params
+        .items
+        .iter()
+        .position(|item| [BACK_TRANSITION, RESUME_ACTION, QUIT_ACTION].contains(&item.action.as_str()))
+        .map(|idx| idx + offset)
+}
+
+/// One menu item per setting, then the items of the params.
+/// The items show the current values, the app saves each change.
+pub fn settingsload(
+    level: &Level,
+    _state: &mut GameState,
+    systems: &mut Systems,
+    _ctx: Option<&mut Context>,
+) -> World {
     let mut wb = WorldBuilder::new(level.name.clone());
 
-    let center = Position {
-        x: state.screen.x / 2.0,
-        y: state.screen.y / 2.0,
+    let mut items: Vec<(String, menu::MenuAction)> = settings::Setting::ALL
+        .iter()
+        .map(|setting| (systems.settings.describe(*setting), menu::MenuAction::Setting(*setting)))
+        .collect();
+    let back = back_item(level, items.len());
+    items.extend(menu_items(level));
+    wb.add_list_page(level, &items, back, systems);
+
+    wb.add_default_camera();
+    wb.build(systems)
+}
+
+/// One menu item per control of `input::CONTROLS` of the player of the params,
+/// then the items of the params.
+/// Confirming a control waits for its new key or button, the app does the rebinding.
+pub fn controlsload(
+    level: &Level,
+    _state: &mut GameState,
+    systems: &mut Systems,
+    _ctx: Option<&mut Context>,
+) -> World {
+    let mut wb = WorldBuilder::new(level.name.clone());
+
+    let player = level.params.player.min(MAX_PLAYERS - 1);
+    let mut items: Vec<(String, menu::MenuAction)> = input::CONTROLS
+        .iter()
+        .enumerate()
+        .map(|(i, control)| {
+            let action = menu::MenuAction::Rebind { player, control: i };
+            (systems.settings.player_input(player).describe(control), action)
+        })
+        .collect();
+    let back = back_item(level, items.len());
+    items.extend(menu_items(level));
+    wb.add_list_page(level, &items, back, systems);
+
+    wb.add_default_camera();
+    wb.build(systems)
+}
+
+/// Asks for a name when the last run made it to the highscores, skipped otherwise.
+pub fn nameentryload(
+    level: &Level,
+    state: &mut GameState,
+    systems: &mut Systems,
+    _ctx: Option<&mut Context>,
+) -> World {
+    let mut wb = WorldBuilder::new(level.name.clone());
+    wb.add_default_camera();
+
+    let run = match state.last_run {
+        Some(run) if systems.highscores.qualifies(run.score) => run,
+        _ => {
+            // nothing to highlight in the table, the last entry is from an older run.
+            systems.highscores.last_rank = None;
+            let camera_id = wb.w.camera_atr_id;
+            wb.add_effect_to_actor(&camera_id, level.get_transition_effect("next".to_string(), 0.0), false);
+            return wb.build(systems);
+        }
     };
 
-    let id = wb.add_text("Game Over".to_string(), text::title_style(), &center, true, systems);
+    let page = wb.add_page_layout();
+    wb.add_layout_text(page, level.params.text(0), text::title_style(), systems);
+    wb.add_layout_text(page, format!("Score: {}", run.score), text::tuto_style(), systems);
+    wb.add_layout_space(page, PAGE_SPACING);
+
+    let entry = highscore::NameEntry::new();
+    let id = wb.add_layout_text(page, entry.display(), text::title_style(), systems);
+    let next_scene_idx = level.get_transition("next");
+    wb.add_effect_to_actor(&id, effect::Effect::EnterName { entry, next_scene_idx }, false);
+
+    wb.add_layout_space(page, PAGE_SPACING);
+    wb.add_layout_prompt(page, level.params.text(1), text::tuto_style(), systems);
+
+    wb.build(systems)
+}
+
+/// The highscore table, the entry just made is highlighted.
+pub fn highscoresload(
+    level: &Level,
+    _state: &mut GameState,
+    systems: &mut Systems,
+    _ctx: Option<&mut Context>,
+) -> World {
+    let mut wb = WorldBuilder::new(level.name.clone());
+
+    let page = wb.add_page_layout();
+    let id = wb.add_layout_text(page, level.params.text(0), text::title_style(), systems);
     wb.add_effect_to_actor(
         &id,
-        level.get_transition_effect("next".to_string(), 3.0),
+        level.get_transition_effect("next".to_string(), level.params.duration),
         false,
     );
-    
+    wb.add_layout_space(page, PAGE_SPACING);
+
+    let entries = systems.highscores.entries().to_vec();
+    if entries.is_empty() {
+        wb.add_layout_text(page, level.params.text(1), text::tuto_style(), systems);
+    }
+    for (rank, e) in entries.iter().enumerate() {
+        let line = format!(
+            "{:>2}. {:<3} {:>6}  level {:<2} {}",
+            rank + 1,
+            e.name,
+            e.score,
+            e.level,
+            highscore::format_date(e.date)
+        );
+        let mut style = text::tuto_style();
+        if systems.highscores.last_rank == Some(rank) {
+            style.color = color::SKYBLUE;
+        }
+        wb.add_layout_text(page, line, style, systems);
+    }
 
     wb.add_default_camera();
-    wb.build(systems, ctx)
+    wb.build(systems)
 }
 
 pub fn victoryload(
     level: &Level,
     state: &mut GameState,
     systems: &mut Systems,
-    ctx: &mut Context,
+    _ctx: Option<&mut Context>,
 ) -> World {
     state.level += 1;
 
     let mut wb = WorldBuilder::new(level.name.clone());
 
-    let center = Position {
-        x: state.screen.x / 2.0,
-        y: state.screen.y / 2.0,
-    };
-
-    let id = wb.add_text("Victory".to_string(), text::title_style(), &center, true, systems);
+    let page = wb.add_page_layout();
+    let id = wb.add_layout_text(page, level.params.text(0), text::title_style(), systems);
     wb.add_effect_to_actor(
         &id,
-        level.get_transition_effect("next".to_string(), 3.0),
+        level.get_transition_effect("next".to_string(), level.params.duration),
         false,
     );    
 
     wb.add_default_camera();
-    wb.build(systems, ctx)
+    wb.build(systems)
 }
 
 pub fn playload(
     level: &Level,
     state: &mut GameState,
     systems: &mut Systems,
-    ctx: &mut Context,
+    ctx: Option<&mut Context>,
 ) -> World {
     let mut wb = WorldBuilder::new(level.name.clone());
 
-    let mut rng = rand::thread_rng();
+    let mut rng = state.level_rng();
+    // SOUNDS, from a seed of their own so the level is built the same with or without audio.
+    if let Some(ctx) = ctx {
+        let sounds = synth::LevelSounds::new(state.level_sound_seed());
+        for (group, samples) in sounds.render() {
+            systems.mixer.set_synth_group(group, &samples, ctx);
+        }
+    }
+    for pilot in &mut state.pilots {
+        pilot.shield = state.max_shield;
+    }
     let state_level = state.level + 3;
     let mut debug_mb = render::MeshBuilderOps::new();
 
     wb.set_size(Size {
         x: ((state_level + 3) as f32) * 1000.0,
-        y: 720.0,
+        y: state.screen.y,
     });
 
     // PLAYER part 1
@@ -639,9 +1451,17 @@ pub fn playload(
         &section_length,
         &height_bounds,
         first_section_length,
+        &mut rng,
     );
     let (top, bottom) = terrain::convert_to_polygons(&height_ranges, &xpositions, &wb.w.size);    
     let cells = terrain::convert_to_cells(&height_ranges, &xpositions);    
+    let camera_mode = level.params.camera.unwrap_or(camera::CameraMode::AutoScroll { speed: SCROLL_SPEED });
+    let checkpoint_cells = terrain::checkpoint_cells(&cells, CHECKPOINT_SPACING);
+    wb.w.checkpoints = iter::once(0)
+        .chain(checkpoint_cells.iter().copied())
+        .map(|i| cells[i].get_center())
+        .collect();
+    wb.w.lose_level = Some(level.get_transition("lose"));
 
     // BACKGROUND.
     {
@@ -666,10 +1486,8 @@ pub fn playload(
         mm.add_poly(&top, &color::BLACK);
         mm.add_polyline(&top, &color::DARKERBLUE, 2.0);        
         a.add_drawable(systems.renderer_source.add_mesh_model(mm));
-        a.collision = actors::mk_polycol(&top);
-        a.on_collision.push(level.get_transition_effect("lose".to_string(), 0.0));
-        a.ticking = true;
         wb.add_to_world(a);
+        wb.add_terrain_segments(&top);
 
         //TUNNEL BOTTOM
         let mut a = actors::ActorType::Background.make();
@@ -677,10 +1495,8 @@ pub fn playload(
         mm.add_poly(&bottom, &color::BLACK);
         mm.add_polyline(&bottom, &color::DARKERBLUE, 2.0);
         a.add_drawable(systems.renderer_source.add_mesh_model(mm));
-        a.collision = actors::mk_polycol(&bottom);
-        a.on_collision.push(level.get_transition_effect("lose".to_string(), 0.0));
-        a.ticking = true;
         wb.add_to_world(a);
+        wb.add_terrain_segments(&bottom);
 
         let nbsteps   = 30;
         let mut bg_colors = color::fade_to(nbsteps, &color::MEDIUMBLUE, &color::DARKBLUE);
@@ -703,26 +1519,55 @@ pub fn playload(
     }
 
     // PLAYER part 2
-    {
+    // one ship per player still in the run, spread across the first cell.
+    let pilot_count = state.pilots.len();
+    for (pilot, cockpit_color) in PILOT_COLORS.iter().enumerate().take(pilot_count) {
+        if state.pilots[pilot].lives <= 0 {
+            wb.w.players.push(no_id());
+            continue;
+        }
         let c = &cells[0];
-        let player_start = c.get_center();
+        let player_start = c.get_point(0.5, (pilot + 1) as f32 / (pilot_count + 1) as f32);
 
         let mut a = actors::ActorType::Player.make();
-        a.collision = actors::mk_polycol(&ship_pts);
+        a.set_collision(actors::mk_polycol(&ship_pts));
+        // the ships take the hits, hazards only sit in their mask.
+        a.on_collision.push(effect::Effect::HitPlayer { actor_id: a.id });
+        a.on_collision.push(effect::Effect::Shake { trauma: HIT_TRAUMA });
         let mut mm = render::MeshModel::new();
-        mm.add_poly(&mesh_gen::cockpit_ship(ship_size), &color::SKYBLUE);
+        mm.add_poly(&mesh_gen::cockpit_ship(ship_size), cockpit_color);
         mm.add_poly(&ship_pts, &color::GREY);
         
         a.add_drawable(systems.renderer_source.add_mesh_model(mm));
         let player_actor_id = wb.add_to_world(a);
-        wb.w.player_atr_id = player_actor_id.clone();
+        wb.w.players.push(player_actor_id);
 
-        let eff = effect::Effect::MoveActor {
-            actor_id: player_actor_id,
-            vector: Position { x: 2.0, y: 0.0 },
+        // the ship keeps up with a scrolling camera, it flies on its own otherwise.
+        if let camera::CameraMode::AutoScroll { speed } = camera_mode {
+            let eff = effect::Effect::MoveActor {
+                actor_id: player_actor_id,
+                vector: Position { x: speed, y: 0.0 },
+            };
+            wb.add_effect_to_actor(&player_actor_id, eff, false);
+        }
+        let eff = effect::Effect::ProcessInput {
+            model: level.params.flight.unwrap_or(state.pilots[pilot].flight_model),
+            velocity: Vector2::zeros(),
+            pilot,
+        };
+        wb.add_effect_to_actor(&player_actor_id, eff, false);
+
+        let mut mm = render::MeshModel::new();
+        mm.add_poly(&mesh_gen::projectile(actors::PROJECTILE_SIZE), &color::WHITE);
+        let eff = effect::Effect::Fire {
+            drawable: systems.renderer_source.add_mesh_model(mm),
+            muzzle: Position { x: 3.0 * ship_size, y: 0.0 },
+            cooldown: 0.25,
+            timer: 0.0,
+            pilot,
+            sound: systems.mixer.group(sound::FIRE_SOUND),
         };
         wb.add_effect_to_actor(&player_actor_id, eff, false);
-        wb.add_effect_to_actor(&player_actor_id, effect::Effect::ProcessInput, false);
         let eff = effect::Effect::PlaceActor {
             actor_id: player_actor_id,
             position: player_start,
@@ -740,13 +1585,12 @@ pub fn playload(
 
         let before_last = cells2.len() - 2;
         for c in cells2.iter_mut().skip(1).take(before_last) {
-            let dist = rng.gen_range(min_size, max_size) as f32;
+            let dist: f32 = rng.gen_range(min_size, max_size);
 
             // DECORATIONS.
             let decoration_height = 10.0f32;
             let side_bounds = Bounds1D::<i32>::new(7, 30);
             let dist_bounds = Bounds1D::<f32>::new(3.0, decoration_height);
-            let eff_on_col = level.get_transition_effect("lose".to_string(), 0.0);
             if c.get_shrinked_y(decoration_height)
                 .can_contains(ship_radius)
             {
@@ -770,9 +1614,8 @@ pub fn playload(
                 a.add_drawable(
                     systems.renderer_source.add_mesh_model(mm)                        
                 );
-                a.collision = actors::mk_polycol(&pts11);
-                a.on_collision.push(eff_on_col);
-                a.transform = bottom_slice.get_point(0.0, 0.0);
+                a.set_collision(actors::mk_polycol(&pts11));
+                a.transform.position = bottom_slice.get_point(0.0, 0.0);
                 // wb.debug_pos(&a.transform);
                 wb.add_to_world(a);
 
@@ -797,9 +1640,8 @@ pub fn playload(
                 a.add_drawable(
                     systems.renderer_source.add_mesh_model(mm)           
                 );
-                a.collision = actors::mk_polycol(&pts11);
-                a.on_collision.push(eff_on_col);
-                a.transform = top_slice.get_point(0.0, 0.0);
+                a.set_collision(actors::mk_polycol(&pts11));
+                a.transform.position = top_slice.get_point(0.0, 0.0);
                 // wb.debug_pos(&a.transform);
                 wb.add_to_world(a);
 
@@ -856,7 +1698,7 @@ pub fn playload(
                     debug_mb = debug_mb.polyline(&dbg_pts, 2.0, color::RED)
                 };
 
-                let id = wb.add_antagonist(max_size);
+                let id = wb.add_antagonist(max_size, &mut rng);
                 wb.add_effect_to_actor(
                     &id,
                     effect::Effect::ResetActor {
@@ -886,82 +1728,180 @@ pub fn playload(
                     )
                 };
 
-                a.collision = actors::mk_polycol(&pts);
+                a.set_collision(actors::mk_polycol(&pts));
                            
                 // let draw_id = systems.renderer.add_dynamic_poly(&pts, color);
                 let mut mm = render::MeshModel::new();
                 mm.add_poly(&pts, &color);
                 a.add_drawable(systems.renderer_source.add_mesh_model(mm));
 
-                let eff_on_col = if is_enemy {
-                    level.get_transition_effect("lose".to_string(), 0.0)
+                // shot rocks and crystals are destroyed with the projectile, crystals are lost.
+                if is_enemy {
+                    a.on_shot.push(effect::Effect::Shake { trauma: SHOT_TRAUMA });
+                    a.mask = actors::LAYER_PROJECTILE;
                 } else {
-                    effect::Effect::KillActor {
-                        actor_id: a.id.clone(),
-                    }
-                };
-                a.on_collision.push(eff_on_col);
+                    a.on_collision.push(effect::Effect::KillActor { actor_id: no_id() });
+                    a.layer = actors::LAYER_PICKUP;
+                    a.mask = actors::LAYER_PLAYER | actors::LAYER_PROJECTILE;
+                }
+                a.on_shot.push(effect::Effect::DespawnActor);
+                a.on_shot.push(effect::Effect::DespawnOther { actor_id: no_id() });
 
-                let sound_oidx         = systems.get_sound("/Randomize6.wav");
-                if let Some(sound_idx) = sound_oidx {
-                    a.on_collision.push(effect::Effect::PlaySound(*sound_idx));
+                // rocks crash with the hit effects, crystals ring when taken.
+                if let (false, Some(group)) = (is_enemy, systems.mixer.group(sound::PICKUP_SOUND)) {
+                    a.on_collision.push(effect::Effect::PlaySound(group));
                 }
+                if let Some(group) = systems.mixer.group(sound::CRASH_SOUND) {
+                    a.on_shot.push(effect::Effect::PlaySound(group));
+                }
+
+                a.transform.position = pos;
 
-                a.transform = pos;
+                let anim = if is_enemy {
+                    effect::Effect::Spin { speed: rng.gen_range(-1.5, 1.5) }
+                } else {
+                    effect::Effect::Sway { amplitude: 0.15, speed: rng.gen_range(1.5, 3.0), time: rng.gen_range(0.0, 6.0) }
+                };
+                wb.add_effect_to_actor(&id, anim, false);
             }
         }
     }
 
+    // CHECKPOINT GATES
+    for i in &checkpoint_cells {
+        let c = &cells[*i];
+        let mut a = actors::ActorType::Background.make();
+        a.layer = actors::LAYER_NONE;
+        a.mask = actors::LAYER_NONE;
+        let mut mm = render::MeshModel::new();
+        mm.add_polyline(&vec![c.get_point(0.5, 0.0), c.get_point(0.5, 1.0)], &color::GREEN, 2.0);
+        a.add_drawable(systems.renderer_source.add_mesh_model(mm));
+        wb.add_to_world(a);
+    }
+
     // END TRIGGER
 
     let mut a = actors::ActorType::Foreground.make();
     let c = cells.iter().nth(cells.len() - 1).unwrap();
-    a.collision = actors::mk_polycol(&c.get_points());
+    a.set_collision(actors::mk_polycol(&c.get_points()));
+    a.layer = actors::LAYER_TRIGGER;
     a.on_collision
         .push(level.get_transition_effect("win".to_string(), 0.0));
     wb.add_to_world(a);
 
     // CAMERA
-    let camera_start = Position {
-        x: 0 as f32,
-        y: 0 as f32,
-    };
-    let camera_id = wb.add_camera();
-    let eff = effect::Effect::MoveActor {
-        actor_id: camera_id,
-        vector: Position { x: -2.0, y: 0.0 },
-    };
-    wb.add_effect_to_actor(&camera_id, eff, false);
-    let eff = effect::Effect::PlaceActor {
-        actor_id: camera_id,
-        position: camera_start,
-    };
-    wb.add_effect_to_actor(&camera_id, eff, true);
-    wb.w.camera_atr_id = camera_id;
+    wb.add_camera();
+    wb.w.camera = camera::Camera::new(camera_mode, state.screen);
 
     // UI
+    // alone, the status of the player is on the right. Together, each player has a corner.
     {
-        let title_ui_pos = Position { x: 10.0, y: 10.0 };
-        let text_id = wb.add_text("Pulsar 3".to_string(), text::ui_style(), &title_ui_pos, false, systems);
-
-        let margin = 10.0;
-        let mut score_ui_pos  = title_ui_pos.clone();
-        if let Some(title_ui_actor) = wb.get_actor(&text_id) {
-            if let Some(tm) = systems.renderer_source.get_text_model(&title_ui_actor.get_drawable()){
-                let (w, h) = tm.get_screen_size(&systems.renderer, ctx);
-                score_ui_pos.x = title_ui_actor.transform.x + (w as f32) + margin;            
+        let solo = pilot_count == 1;
+        let hud = wb.add_layout(
+            layout::Layout::new(if solo { layout::Anchor::TopLeft } else { layout::Anchor::Top }, layout::Direction::Horizontal)
+                .with_padding(HUD_PADDING)
+                .with_spacing(HUD_PADDING),
+        );
+        wb.add_layout_text(hud, level.params.text(0), text::ui_style(), systems);
+
+        for (pilot, corner) in PILOT_CORNERS.iter().enumerate().take(pilot_count) {
+            let corner = if solo { layout::Anchor::TopRight } else { *corner };
+            let status = wb.add_layout(
+                layout::Layout::new(corner, layout::Direction::Horizontal)
+                    .with_padding(HUD_PADDING)
+                    .with_spacing(HUD_PADDING),
+            );
+            let score_layout = if solo { hud } else { status };
+            let text_id = wb.add_layout_text(score_layout, String::new(), text::ui_style(), systems);
+            wb.add_effect_to_actor(&text_id, effect::Effect::SetScore { new_value: 0 }, true);
+            wb.add_effect_to_actor(
+                &text_id,
+                effect::Effect::UpdateScore { actor_id: text_id, pilot },
+                false,
+            );
+
+            if state.max_shield > 0 {
+                let shield_id = wb.add_layout_text(status, String::new(), text::ui_style(), systems);
+                wb.add_effect_to_actor(&shield_id, effect::Effect::UpdateShield { pilot }, false);
             }
-            
+            let lives_id = wb.add_layout_text(status, String::new(), text::ui_style(), systems);
+            wb.add_effect_to_actor(&lives_id, effect::Effect::UpdateLives { pilot }, false);
         }
+    }
 
-        let text_id = wb.add_text("Score: 0".to_string(), text::ui_style(), &score_ui_pos, false, systems);
-        wb.add_effect_to_actor(&text_id, effect::Effect::SetScore { new_value: 0 }, true);
-        wb.add_effect_to_actor(
-            &text_id,
-            effect::Effect::UpdateScore { actor_id: text_id },
-            false,
-        );
+    wb.build(systems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay;
+
+    const SCREEN: Size = Size {
+        x: 1280.0,
+        y: 720.0,
+    };
+    const DT: f32 = 1.0 / 120.0;
+
+    /// A play level whose transitions lead to an empty scene.
+    fn play_level() -> Level {
+        let end = Level::new("end".to_string());
+        let mut play = Level::new("play".to_string());
+        play.loader = playload;
+        play.add_transition(&"win".to_string(), &end);
+        play.add_transition(&"lose".to_string(), &end);
+        play
     }
 
-    wb.build(systems, ctx)
+    /// Plays the first level of `seed` for `steps` steps, weaving up and down while firing.
+    /// Returns the score, the lives and where every actor ended.
+    fn run(seed: u64, steps: usize) -> (i32, i32, Vec<Transform>) {
+        let mut state = GameState::new(SCREEN, seed);
+        let mut systems = Systems::new();
+        let mut world = play_level().load(&mut state, &mut systems, None);
+        world.start(&state, &mut systems);
+        for i in 0..steps {
+            let input = InputState {
+                xaxis: 0.3,
+                yaxis: if (i / 60) % 2 == 0 { 0.5 } else { -0.5 },
+                fire: i % 20 < 10,
+                ..InputState::default()
+            };
+            let wc = world.step(DT, &[input], &mut state, &mut systems);
+            if wc.level.is_some() {
+                break;
+            }
+        }
+        let pilot = &state.pilots[0];
+        let transforms = world.actors().iter().map(|a| a.transform).collect();
+        (pilot.score, pilot.lives, transforms)
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        assert_eq!(run(42, 600), run(42, 600));
+    }
+
+    /// The demo of the attract mode goes through its level without a hit,
+    /// to be written again when the levels change.
+    #[test]
+    fn demo_flies_clear() {
+        let script = replay::Script::from_ron(include_str!("resources/demo.ron")).unwrap();
+        let replay = script.to_replay(DT);
+        let mut state = GameState::new(SCREEN, replay.seed);
+        state.level = replay.level;
+        let mut systems = Systems::new();
+        let mut world = play_level().load(&mut state, &mut systems, None);
+        world.start(&state, &mut systems);
+        for frame in &replay.frames {
+            let wc = world.step(frame.dt, &frame.inputs(), &mut state, &mut systems);
+            assert!(wc.level.is_none());
+        }
+        assert_eq!(state.pilots[0].shield, state.max_shield);
+    }
+
+    #[test]
+    fn seed_picks_the_level() {
+        assert_ne!(run(42, 1).2, run(43, 1).2);
+    }
 }
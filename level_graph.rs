@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::level;
+
+/// A scene of the level graph file, as written by the designers.
+#[derive(Deserialize)]
+pub struct LevelDesc {
+    pub name: String,
+    pub loader: String,
+    #[serde(default)]
+    pub transitions: HashMap<String, String>,
+    #[serde(default)]
+    pub params: level::LoaderParams,
+}
+
+/// The whole scene graph : every level, and the one the game starts on.
+#[derive(Deserialize)]
+pub struct LevelGraphDesc {
+    pub start: String,
+    pub levels: Vec<LevelDesc>,
+}
+
+/// The loaders a level can refer to, with the transitions each one triggers.
+pub fn find_loader(name: &str) -> Option<(level::LevelLoader, &'static [&'static str])> {
+    match name {
+        "empty" => Some((level::emptyload, &[])),
+        "intro" => Some((level::introload, &["next"])),
+        "tuto" => Some((level::tutoload, &["next"])),
+        "play" => Some((level::playload, &["win", "lose"])),
+        "gameover" => Some((level::gameoverload, &["next"])),
+        "victory" => Some((level::victoryload, &["next"])),
+        "nameentry" => Some((level::nameentryload, &["next"])),
+        "highscores" => Some((level::highscoresload, &["next"])),
+        "menu" => Some((level::menuload, &[])),
+        "settings" => Some((level::settingsload, &[])),
+        "controls" => Some((level::controlsload, &[])),
+        _ => None,
+    }
+}
+
+pub fn parse(s: &str) -> Result<LevelGraphDesc, String> {
+    ron::de::from_str(s).map_err(|e| format!("{}", e))
+}
+
+/// Checks the graph for unknown loaders, dangling or missing transitions.
+/// All the problems found are reported at once, one per line.
+pub fn validate(desc: &LevelGraphDesc) -> Result<(), String> {
+    let mut errors = Vec::<String>::new();
+    let mut names = HashMap::<&str, usize>::new();
+    for l in &desc.levels {
+        *names.entry(l.name.as_str()).or_insert(0) += 1;
+    }
+    for (name, count) in &names {
+        if *count > 1 {
+            errors.push(format!("level '{}' is defined {} times", name, count));
+        }
+    }
+    if !names.contains_key(desc.start.as_str()) {
+        errors.push(format!("start level '{}' does not exist", desc.start));
+    }
+
+    for l in &desc.levels {
+        for (transition, target) in &l.transitions {
+            if !names.contains_key(target.as_str()) {
+                errors.push(format!(
+                    "level '{}': transition '{}' leads to unknown level '{}'",
+                    l.name, transition, target
+                ));
+            }
+        }
+        for item in &l.params.items {
+            if !level::BUILTIN_ACTIONS.contains(&item.action.as_str())
+                && !l.transitions.contains_key(&item.action)
+            {
+                errors.push(format!(
+                    "level '{}': menu item '{}' uses unknown transition '{}'",
+                    l.name, item.label, item.action
+                ));
+            }
+        }
+        match find_loader(&l.loader) {
+            None => errors.push(format!("level '{}': unknown loader '{}'", l.name, l.loader)),
+            Some((_, required)) => {
+                for transition in required.iter() {
+                    if !l.transitions.contains_key(*transition) {
+                        errors.push(format!(
+                            "level '{}': loader '{}' needs a '{}' transition",
+                            l.name, l.loader, transition
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Validates the graph and builds its levels, the start level first.
+pub fn build_levels(desc: &LevelGraphDesc) -> Result<Vec<level::Level>, String> {
+    validate(desc)?;
+
+    let mut levels = Vec::<level::Level>::new();
+    for l in &desc.levels {
+        let mut lvl = level::Level::new(l.name.clone());
+        lvl.loader = find_loader(&l.loader).unwrap().0;
+        lvl.params = l.params.clone();
+        levels.push(lvl);
+    }
+
+    let idx_by_name: HashMap<String, usize> = desc
+        .levels
+        .iter()
+        .enumerate()
+        .map(|(i, l)| (l.name.clone(), i))
+        .collect();
+    for (i, l) in desc.levels.iter().enumerate() {
+        for (transition, target) in &l.transitions {
+            let target_level = levels[idx_by_name[target]].clone();
+            levels[i].add_transition(transition, &target_level);
+        }
+    }
+
+    let start_idx = idx_by_name[&desc.start];
+    let start = levels.remove(start_idx);
+    levels.insert(0, start);
+    Ok(levels)
+}
+
+pub fn load_levels(s: &str) -> Result<Vec<level::Level>, String> {
+    build_levels(&parse(s)?)
+}
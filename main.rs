@@ -3,16 +3,20 @@ extern crate find_folder;
 // use cgmath;
 use std::env;
 use std::path;
+use std::io::Read;
 use std::collections::HashMap;
 
 use ggez::{Context, ContextBuilder, GameResult};
 use ggez::event::{self, EventHandler, Axis, Button};
-use ggez::input::gamepad::GamepadId;
+use ggez::input::gamepad::{self, GamepadId};
 use ggez::input::keyboard::KeyCode;
 use ggez::event::KeyMods;
 use ggez::graphics;
 use ggez::conf;
-use ggez::audio;
+use ggez::filesystem;
+use ggez::timer;
+use serde::{Serialize, Deserialize};
+use rand::Rng;
 
 use crate::unit::*;
 
@@ -31,16 +35,32 @@ mod terrain;
 mod tunnel;
 mod cell;
 mod mesh_gen;
+mod replay;
+mod level_graph;
+mod broadphase;
+mod highscore;
+mod menu;
+mod layout;
+mod flight;
+mod camera;
+mod settings;
+mod input;
+mod sound;
+mod synth;
 /// **********************************************************************
 /// The `InputState` is exactly what it sounds like, it just keeps track of
 /// the user's input state so that we turn keyboard events into something
 /// state-based and device-independent.
 /// **********************************************************************
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct InputState {
     pub xaxis: f32,
     pub yaxis: f32,
     pub fire: bool,
+    #[serde(default)]
+    pub confirm: bool,
+    #[serde(default)]
+    pub back: bool,
 }
 
 impl Default for InputState {
@@ -49,23 +69,158 @@ impl Default for InputState {
             xaxis: 0.0,
             yaxis: 0.0,
             fire: false,
+            confirm: false,
+            back: false,
         }
     }
 }
 
+/// Lives a run starts with.
+const START_LIVES : i32 = 3;
+/// Hits the shield takes in each level, 0 plays without a shield.
+const MAX_SHIELD  : i32 = 1;
+/// Players flying together, each with a ship of their own.
+pub const MAX_PLAYERS : usize = 2;
+
+/// What a player of the run has : their input, score, lives and shield.
+#[derive(Clone)]
+pub struct Pilot{
+    pub input  : InputState,
+    pub score  : i32,
+    pub lives  : i32,
+    /// Refilled to `max_shield` at the start of each level.
+    pub shield : i32,
+    /// Flight model of their ship in the levels that do not pick one, kept for the whole run.
+    pub flight_model : flight::FlightModel,
+}
+
+impl Pilot{
+    fn new(lives : i32, shield : i32, flight_model : flight::FlightModel) -> Self{
+        Pilot{
+            input : InputState::default(),
+            score : 0,
+            lives,
+            shield,
+            flight_model,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct GameState{
-    score     : i32,    
+    /// What the menus read, every device through the bindings of the first player.
     input     : InputState,
+    /// One per player of the run.
+    pilots    : Vec::<Pilot>,
     level     : i32,
+    seed      : u64,
     screen    : Size,
-    paused    : bool
+    start_lives : i32,
+    max_shield  : i32,
+    /// Flight model of the settings, the players of a new run get it.
+    flight_model : flight::FlightModel,
+    /// Set on game over, until the next one.
+    last_run  : Option<highscore::FinishedRun>,
+}
+
+impl GameState{
+    pub fn new(screen : Size, seed : u64) -> Self{
+        GameState{
+            input : InputState::default(),
+            pilots : vec![Pilot::new(START_LIVES, MAX_SHIELD, flight::FlightModel::default())],
+            level : 0,
+            seed,
+            screen,
+            start_lives : START_LIVES,
+            max_shield  : MAX_SHIELD,
+            flight_model : flight::FlightModel::default(),
+            last_run : None,
+        }
+    }
+
+    /// Starts the run over with `count` players.
+    pub fn set_players(&mut self, count : usize){
+        let count = count.clamp(1, MAX_PLAYERS);
+        self.pilots = (0..count).map(|_| Pilot::new(self.start_lives, self.max_shield, self.flight_model)).collect();
+    }
+
+    /// Ends the run, the next one starts at the first level of a new seed with as many players.
+    pub fn reset_run(&mut self){
+        self.level = 0;
+        self.set_players(self.pilots.len());
+        self.seed = rand::random();
+    }
+
+    pub fn flight_models(&self) -> Vec::<flight::FlightModel>{
+        self.pilots.iter().map(|p| p.flight_model).collect()
+    }
+
+    /// Tells the players apart on the HUD, nothing when playing alone.
+    pub fn pilot_tag(&self, pilot : usize) -> String{
+        if self.pilots.len() > 1 { format!("P{} ", pilot + 1) } else { String::new() }
+    }
+
+    /// The best score of the run, the one the highscores keep.
+    pub fn best_score(&self) -> i32{
+        self.pilots.iter().map(|p| p.score).max().unwrap_or(0)
+    }
+
+    /// Back to `saved`, the inputs are left as the devices hold them.
+    pub fn restore(&mut self, saved : &GameState){
+        let input  = self.input;
+        let inputs : Vec::<InputState> = self.pilots.iter().map(|p| p.input).collect();
+        *self = saved.clone();
+        self.input = input;
+        for (pilot, input) in self.pilots.iter_mut().zip(inputs) {
+            pilot.input = input;
+        }
+    }
+
+    /// The shield of `pilot` as a bar of pips, `[##-]`.
+    pub fn shield_meter(&self, pilot : usize) -> String{
+        let shield = self.pilots.get(pilot).map_or(0, |p| p.shield);
+        let full  = shield.max(0) as usize;
+        let empty = (self.max_shield - shield).max(0) as usize;
+        format!("[{}{}]", "#".repeat(full), "-".repeat(empty))
+    }
+
+    /// The rng used to generate the current level.
+    /// It only depends on the run seed and the level number,
+    /// so reporting both is enough to rebuild the exact same level.
+    pub fn level_rng(&self) -> GameRng {
+        let level_salt = (self.level as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        seeded_rng(self.seed ^ level_salt)
+    }
+
+    /// The seed the sounds of the current level are synthesized from.
+    pub fn level_sound_seed(&self) -> u64 {
+        self.level_rng().gen()
+    }
+}
+
+/// The run seed can be forced with the `DEAD_PULSAR_SEED` environment variable,
+/// otherwise a random one is picked.
+fn initial_seed() -> u64 {
+    if let Ok(seed_str) = env::var("DEAD_PULSAR_SEED") {
+        if let Ok(seed) = seed_str.parse::<u64>() {
+            return seed;
+        }
+        println!("Invalid DEAD_PULSAR_SEED '{}', using a random seed.", seed_str);
+    }
+    rand::random()
 }
 
 pub struct Systems{
     pub renderer_source : render::RendererSource,
     pub renderer    : render::Renderer,
-    pub sounds      : Vec::<audio::Source>,
-    pub sound_names : HashMap::<String, usize>
+    pub mixer       : sound::Mixer,
+    pub highscores  : highscore::Highscores,
+    /// Menus of the current world, run by `Effect::Menu`.
+    pub menus       : KeyedResource::<menu::Menu>,
+    pub settings    : settings::Settings,
+    /// Templates of the prompts of the current world, by text model.
+    pub prompts     : KeyedResource::<String>,
+    pub prompt_style : input::PromptStyle,
 }
 
 impl Systems{
@@ -73,32 +228,77 @@ impl Systems{
         Systems{
             renderer_source : render::RendererSource::new(),
             renderer    : render::Renderer::new(),
-            sounds      : Vec::<audio::Source>::new(),
-            sound_names : HashMap::<String, usize>::new()
+            mixer       : sound::Mixer::new(),
+            highscores  : highscore::Highscores::new(),
+            menus       : KeyedResource::<menu::Menu>::new(),
+            settings    : settings::Settings::default(),
+            prompts     : KeyedResource::<String>::new(),
+            prompt_style : input::PromptStyle::Keyboard,
         }
     }
-    fn add_sound(&mut self, rel_path : String,  ctx : &mut Context) {        
-        let sound = audio::Source::new(ctx, rel_path.clone()).unwrap();
-        self.sounds.push(sound);
-        self.sound_names.insert(rel_path, self.sounds.len() - 1);        
-    }
-    fn get_sound(&self, sound_name: &str) -> Option<&usize>{
-        self.sound_names.get(sound_name)
-    }
 }
 
+/// Name of the game, and who made it : ggez and the settings pick the user dirs from them.
+const GAME_TITLE  : &str = "Dead Pulsar";
+const GAME_ID     : &str = "dead pulsar";
+const GAME_AUTHOR : &str = "LBdN";
+
+/// The game is drawn on a screen of this size, scaled to fit the window.
+const VIRTUAL_SCREEN : Size = Size{ x : 1280.0, y : 720.0 };
+/// The window can not be made smaller.
+const MIN_WINDOW : Size = Size{ x : 320.0, y : 180.0 };
+
+/// The world is simulated at this fixed rate, whatever the frame rate.
+const UPDATES_PER_SECOND : u32 = 120;
+/// Steps a single frame may run to catch up.
+const MAX_STEPS_PER_FRAME : u32 = 8;
+
+/// Each run of a world with a player is recorded there, in the user data dir.
+const LAST_RUN_FILE : &str = "last_run.ron";
+/// The highscore table, in the user data dir.
+const HIGHSCORES_FILE : &str = "highscores.ron";
+/// The script of the attract mode, in the resources.
+const DEMO_FILE : &str = "/demo.ron";
+/// A scene with this transition plays the demo in the scene it leads to once left idle.
+const DEMO_TRANSITION : &str = "demo";
+/// Seconds without input before the demo starts.
+const DEMO_IDLE_TIME : f32 = 20.0;
+
 pub struct App {    
     systems: Systems,
     state: Option<GameState>,
     levels : Vec::<level::Level>,
     world : level::World,    
+    /// Level of the world, and the game state before it was loaded, to restart it.
+    current_level : Id,
+    state_at_load : Option<GameState>,
+    /// The pause overlay, stepped instead of the frozen world while it is open.
+    pause : Option<level::World>,
+    /// The mode the window was last given, from the settings.
+    window_mode : conf::WindowMode,
+    /// What the devices hold, turned into the input of the game state through the settings.
+    mapper : input::InputMapper,
+    /// The player and control waiting for a new key or button, and the text model showing it.
+    rebinding : Option<(usize, usize, Id)>,
+    /// The gamepads plugged in, as far as their events tell.
+    /// With several players the n-th pad plugged is the one of the n-th player.
+    gamepads : Vec::<GamepadId>,
+    recorder : Option<replay::Recorder>,
+    playback : Option<replay::Playback>,
+    queued_replay : Option<replay::Replay>,
+    /// The replay of the attract mode, played from the script of the resources if they have one.
+    demo_replay : Option<replay::Replay>,
+    /// While the demo plays, the scene it left and the game state to put back.
+    demo : Option<(Id, GameState)>,
+    /// Seconds since the last input.
+    idle_time : f32,
 }
 
 
 
 
 impl App {
-    pub fn new(ctx: &mut Context, screen : Size) -> App {
+    pub fn new(ctx: &mut Context, screen : Size, settings : settings::Settings) -> App {
 
         let mut fonts = HashMap::<String, graphics::Font>::new();
         fonts.insert("edundot".to_string(), graphics::Font::new(ctx, "/font/edundot.ttf").unwrap());
@@ -108,19 +308,31 @@ impl App {
 
         let mut a = App {
             systems :Systems::new(),            
-            state : Some( GameState{
-                score : 0,
-                input : InputState:: default(),
-                level : 0,
-                screen : screen,
-                paused : false
-            }),           
+            state : Some( GameState::new(screen, initial_seed())),
             levels : Vec::<level::Level>::new(),
-            world : level::World::empty()
+            world : level::World::empty(),
+            current_level : no_id(),
+            state_at_load : None,
+            pause : None,
+            window_mode : settings.window_mode(),
+            mapper : input::InputMapper::new(),
+            rebinding : None,
+            gamepads : Vec::<GamepadId>::new(),
+            recorder : None,
+            playback : None,
+            queued_replay : None,
+            demo_replay : None,
+            demo : None,
+            idle_time : 0.0,
         };
 
-        a.systems.add_sound("/Randomize6.wav".to_string(), ctx);
+        a.systems.mixer.load(ctx);
         a.systems.renderer.fonts = fonts;
+        a.systems.renderer.virtual_size = screen;
+        a.systems.highscores = highscore::Highscores::load(filesystem::user_data_dir(ctx).join(HIGHSCORES_FILE));
+        a.systems.settings = settings;
+        a.demo_replay = load_demo(ctx);
+        a.apply_settings(ctx);
         a
     }
 
@@ -133,60 +345,437 @@ impl App {
         None
     }
 
+    /// Replaces the world by a fresh one built by the level `level_id`.
+    fn load_level(&mut self, level_id : Id, ctx : &mut Context){
+        self.pause = None;
+        self.world.stop();            
+        self.systems.renderer.clear();
+        self.systems.menus.clear();
+        self.systems.prompts.clear();
+        let level = (*self.find_level(&level_id).unwrap()).clone();
+        let mut state = self.state.as_mut().unwrap();
+        self.state_at_load = Some(state.clone());
+        self.current_level = level_id;
+        self.idle_time = 0.0;
+        if let Some(music) = &level.params.music {
+            self.systems.mixer.play_music(music, ctx);
+        }
+        self.world = level.load(&mut state, &mut self.systems, Some(ctx));
+        self.world.start(state, &mut self.systems);
+        self.on_world_loaded(ctx);
+    }
+
+    /// Freezes a world with a player under the pause overlay.
+    fn pause(&mut self, ctx : &mut Context){
+        if self.pause.is_some() || !self.world.has_player() || self.demo.is_some(){
+            return;
+        }
+        let level = match self.levels.iter().find(|l| l.name() == level::PAUSE_LEVEL){
+            Some(level) => level.clone(),
+            None => return
+        };
+        if let Some(state) = self.state.as_mut(){
+            let mut overlay = level.load(state, &mut self.systems, Some(ctx));
+            overlay.start(state, &mut self.systems);
+            self.pause = Some(overlay);
+        }
+    }
+
+    fn resume(&mut self){
+        if let Some(mut overlay) = self.pause.take(){
+            overlay.release(&mut self.systems);
+        }
+    }
+
+    /// Puts the window in the mode of the settings, the flight model in the game state
+    /// and the volumes in the mixer. Vsync is left as the window opened with.
+    fn apply_settings(&mut self, ctx : &mut Context){
+        let settings = &self.systems.settings;
+        self.systems.mixer.set_gains(settings.sfx_gain(), settings.music_gain());
+        if let Some(state) = self.state.as_mut(){
+            state.flight_model = settings.flight_model;
+        }
+        // setting the same mode again would undo the resizing done by hand.
+        let mode = settings.window_mode();
+        if mode != self.window_mode {
+            self.window_mode = mode;
+            if let Err(e) = graphics::set_mode(ctx, mode){
+                println!("Could not change the window mode: {}", e);
+            }
+        }
+        let (width, height) = graphics::drawable_size(ctx);
+        self.systems.renderer.resize(ctx, width, height);
+    }
+
+    fn save_settings(&self){
+        if let Err(e) = self.systems.settings.save(){
+            println!("Could not save settings: {}", e);
+        }
+    }
+
+    fn toggle_fullscreen(&mut self, ctx : &mut Context){
+        self.systems.settings.fullscreen = !self.systems.settings.fullscreen;
+        self.apply_settings(ctx);
+        self.save_settings();
+    }
+
+    /// The menus read every device, a player alone too.
+    /// Otherwise each player reads the keyboard through their bindings, and their own gamepad.
+    fn update_input(&mut self){
+        let state = match self.state.as_mut(){
+            Some(state) => state,
+            None => return
+        };
+        let settings = &self.systems.settings;
+        state.input = self.mapper.input_state(&settings.input, &|_| true);
+        if state.pilots.len() == 1 {
+            state.pilots[0].input = state.input;
+            return;
+        }
+        for (i, pilot) in state.pilots.iter_mut().enumerate() {
+            let pad = self.gamepads.get(i).copied();
+            let accepts = |device : &input::Device| match device {
+                input::Device::Keyboard    => true,
+                input::Device::Gamepad(id) => Some(*id) == pad,
+            };
+            pilot.input = self.mapper.input_state(settings.player_input(i), &accepts);
+        }
+    }
+
+    /// The prompts follow the device played last.
+    fn device_used(&mut self, device : input::Device){
+        self.idle_time = 0.0;
+        self.systems.prompt_style = match device {
+            input::Device::Keyboard   => input::PromptStyle::Keyboard,
+            input::Device::Gamepad(_) => input::PromptStyle::Gamepad,
+        };
+    }
+
+    /// A key or button went down : it is bound to the control waiting for one,
+    /// otherwise the actions it drives are held and the ones acting on a press run.
+    fn source_pressed(&mut self, device : input::Device, source : input::Source, ctx : &mut Context){
+        self.device_used(device);
+        // any key or button ends the demo, and does nothing else.
+        if self.demo.is_some() {
+            self.end_demo(ctx);
+            return;
+        }
+        let actions = self.systems.settings.input.actions_of(&source);
+        if let Some((player, control_idx, label)) = self.rebinding.take(){
+            // escape and the back of the player give up the wait, they can not be lost to a rebinding.
+            let cancel = source == input::Source::key(KeyCode::Escape)
+                || self.systems.settings.player_input(player).actions_of(&source).contains(&input::Action::Back);
+            let control = &input::CONTROLS[control_idx];
+            let map = self.systems.settings.player_input_mut(player);
+            if !cancel {
+                map.rebind(control, source);
+            }
+            let description = map.describe(control);
+            if let Some(tm) = self.systems.renderer_source.textmodels.get_mut(&label){
+                tm.update_string(description);
+            }
+            if !cancel {
+                self.save_settings();
+            }
+            return;
+        }
+        self.mapper.press(device, source);
+        self.update_input();
+        // back leaves the game from the scenes that do nothing with it.
+        if actions.contains(&input::Action::Back) && self.pause.is_none() && !self.world.reads_back() {
+            event::quit(ctx);
+            return;
+        }
+        for action in actions {
+            match action {
+                input::Action::Pause      => self.toggle_pause(ctx),
+                input::Action::Fullscreen => self.toggle_fullscreen(ctx),
+                input::Action::Screenshot => {
+                    let img = graphics::screenshot(ctx).expect("Could not take screenshot");
+                    img.encode(ctx, graphics::ImageFormat::Png, "/screenshot.png")
+                        .expect("Could not save screenshot");
+                },
+                _ => ()
+            }
+        }
+    }
+
+    fn source_released(&mut self, device : input::Device, source : input::Source){
+        self.mapper.release(device, source);
+        self.update_input();
+    }
+
+    /// ggez does not report gamepads connecting, a pad is known from its first event.
+    fn gamepad_seen(&mut self, id : GamepadId, ctx : &Context){
+        if !self.gamepads.contains(&id){
+            println!("Gamepad connected: {}", gamepad::gamepad(ctx, id).name());
+            self.gamepads.push(id);
+        }
+    }
+
+    /// Nor disconnecting : the known pads are checked each frame.
+    /// What an unplugged pad held is let go, and the game pauses if it was the last one
+    /// or a player is left without theirs.
+    fn check_gamepads(&mut self, ctx : &mut Context){
+        let unplugged : Vec::<GamepadId> = self.gamepads.iter()
+            .copied()
+            .filter(|id| !gamepad::gamepad(ctx, *id).is_connected())
+            .collect();
+        if unplugged.is_empty(){
+            return;
+        }
+        for id in unplugged {
+            println!("Gamepad disconnected: {}", gamepad::gamepad(ctx, id).name());
+            self.gamepads.retain(|known| *known != id);
+            self.mapper.forget(input::Device::Gamepad(id));
+        }
+        self.update_input();
+        let players = self.state.as_ref().map_or(1, |state| state.pilots.len());
+        if self.gamepads.is_empty() || players > 1 {
+            self.device_used(input::Device::Keyboard);
+            self.pause(ctx);
+        }
+    }
+
+    /// Shows the control `control_idx` of `player` as waiting, the next key or button pressed is bound to it.
+    fn start_rebinding(&mut self, player : usize, control_idx : usize, label : Id){
+        let control = &input::CONTROLS[control_idx];
+        if let Some(tm) = self.systems.renderer_source.textmodels.get_mut(&label){
+            tm.update_string(format!("{}: press a key or button", control.label));
+        }
+        self.rebinding = Some((player, control_idx, label));
+    }
+
+    fn toggle_pause(&mut self, ctx : &mut Context){
+        if self.pause.is_some(){
+            self.resume();
+        } else {
+            self.pause(ctx);
+        }
+    }
+
+    /// The replay drives the next world with a player.
+    /// The run seed, level, players and their flight models are taken from the replay
+    /// so that world is generated and flown identically.
+    pub fn queue_replay(&mut self, replay : replay::Replay){
+        if let Some(state) = self.state.as_mut(){
+            state.seed  = replay.seed;
+            state.level = replay.level;
+            state.set_players(replay.players);
+            for (pilot, model) in state.pilots.iter_mut().zip(&replay.flight_models) {
+                pilot.flight_model = *model;
+            }
+        }
+        self.queued_replay = Some(replay);
+    }
+
+    fn save_recording(&mut self, ctx : &Context){
+        if let Some(recorder) = self.recorder.take(){
+            let replay = recorder.finish();
+            if replay.frames.is_empty(){
+                return;
+            }
+            let path = filesystem::user_data_dir(ctx).join(LAST_RUN_FILE);
+            match replay.save(&path){
+                Ok(_)  => println!("Replay saved to {}", path.display()),
+                Err(e) => println!("Could not save replay: {}", e)
+            }
+        }
+    }
+
+    /// Plays the demo in the scene the `demo` transition of the current scene leads to.
+    /// The run it plays is not recorded, and the game state is put back when it ends.
+    fn start_demo(&mut self, ctx : &mut Context){
+        self.idle_time = 0.0;
+        if self.demo.is_some() || self.pause.is_some() || self.rebinding.is_some() || self.queued_replay.is_some() {
+            return;
+        }
+        let scene = match self.find_level(&self.current_level).and_then(|l| l.find_transition(DEMO_TRANSITION)) {
+            Some(scene) => scene,
+            None => return
+        };
+        let (replay, state) = match (self.demo_replay.clone(), self.state.clone()) {
+            (Some(replay), Some(state)) => (replay, state),
+            _ => return
+        };
+        self.demo = Some((self.current_level, state));
+        self.queue_replay(replay);
+        self.load_level(scene, ctx);
+    }
+
+    /// Back to the scene the demo left, as it was.
+    fn end_demo(&mut self, ctx : &mut Context){
+        if let Some((scene, saved)) = self.demo.take(){
+            self.playback = None;
+            if let Some(state) = self.state.as_mut(){
+                state.restore(&saved);
+            }
+            self.load_level(scene, ctx);
+        }
+    }
+
+    /// Worlds with a player are either recorded or, if a replay is queued, played back.
+    fn on_world_loaded(&mut self, ctx : &Context){
+        self.save_recording(ctx);
+        self.playback = None;
+        if !self.world.has_player(){
+            return;
+        }
+        if let Some(replay) = self.queued_replay.take(){
+            self.playback = Some(replay::Playback::new(replay));
+        } else if let Some(state) = self.state.as_ref(){
+            self.recorder = Some(replay::Recorder::new(state.seed, state.level, state.flight_models()));
+        }
+    }
+
 }
 
-fn player_handle_input(input : &InputState, pa : &mut actors::Actor, worldbounds : &level::WorldBounds, dt :u128) {
+/// Moves the ship as `model` flies it, `velocity` is the speed it keeps between steps.
+fn player_handle_input(input : &InputState, model : &flight::FlightModel, velocity : &mut Vector2, pa : &mut actors::Actor, worldbounds : &level::WorldBounds, dt : f32) {
 
-    const MOVE_STEP : f32 = 180.5;    
+    // the ship banks toward its vertical direction.
+    const BANK_ANGLE : f32 = 0.35;
+    const BANK_RATE  : f32 = 10.0;
     
-    let movex = input.xaxis * MOVE_STEP * dt as f32 / 1000.0;
-    let movey = input.yaxis * MOVE_STEP  * dt as f32 / 1000.0;
+    model.apply(input, velocity, dt);
         
-    pa.transform.x += movex;
-    pa.transform.y += movey;
-
-    let actor_size = pa.collision.get_size();
-    pa.transform.x = pa.transform.x.min(worldbounds.max.x - actor_size.y);
-    pa.transform.y = pa.transform.y.min(worldbounds.max.y - actor_size.x);
-    pa.transform.x = pa.transform.x.max(worldbounds.min.x);
-    pa.transform.y = pa.transform.y.max(worldbounds.min.y);
+    pa.transform.position.x += velocity.x * dt;
+    pa.transform.position.y += velocity.y * dt;
+
+    let bank = (velocity.y / model.max_speed()).clamp(-1.0, 1.0) * BANK_ANGLE;
+    pa.transform.rotation += (bank - pa.transform.rotation) * (BANK_RATE * dt).min(1.0);
+
+    let actor_size = pa.collision().get_size();
+    let unclamped = pa.transform.position;
+    pa.transform.position.x = pa.transform.position.x.min(worldbounds.max.x - actor_size.y);
+    pa.transform.position.y = pa.transform.position.y.min(worldbounds.max.y - actor_size.x);
+    pa.transform.position.x = pa.transform.position.x.max(worldbounds.min.x);
+    pa.transform.position.y = pa.transform.position.y.max(worldbounds.min.y);
+    // the speed toward a bound is lost against it.
+    if pa.transform.position.x != unclamped.x {
+        velocity.x = 0.0;
+    }
+    if pa.transform.position.y != unclamped.y {
+        velocity.y = 0.0;
+    }
 
-    // println!("{} {} {} {}", pa.transform.x, worldbounds.min.x, pa.transform.y, worldbounds.max.x);
+    // println!("{} {} {} {}", pa.transform.position.x, worldbounds.min.x, pa.transform.position.y, worldbounds.max.x);
     
 }
 
+impl App {
+    /// One simulation step of `dt` seconds, fed by the replay being played if any.
+    fn fixed_step(&mut self, dt : f32) -> level::WorldChange {
+        let state = match self.state.as_mut() {
+            Some(state) => state,
+            None => return level::WorldChange::default()
+        };
+        if let Some(overlay) = self.pause.as_mut(){
+            // the frozen world is neither stepped nor recorded.
+            let input = state.input;
+            return overlay.step(dt, &[input], state, &mut self.systems);
+        }
+        let inputs : Vec::<InputState> = if self.world.has_player() {
+            state.pilots.iter().map(|p| p.input).collect()
+        } else {
+            vec![state.input]
+        };
+        let mut frame = replay::ReplayFrame::new(dt, &inputs);
+        if let Some(playback) = self.playback.as_mut(){
+            if let Some(recorded) = playback.next_frame(){
+                frame = recorded;
+            } else if let Some((scene, _)) = self.demo.as_ref() {
+                // end of the demo, back to the scene it left.
+                return level::WorldChange{ level : Some(*scene), ..level::WorldChange::default() };
+            } else {
+                // end of the replay, the players take over.
+                self.playback = None;
+                state.input = InputState::default();
+                for pilot in &mut state.pilots {
+                    pilot.input = InputState::default();
+                }
+                frame = replay::ReplayFrame::new(dt, &vec![InputState::default(); inputs.len()]);
+            }
+        }
+        if let Some(recorder) = self.recorder.as_mut(){
+            recorder.record(&frame);
+        }
+        self.world.step(frame.dt, &frame.inputs(), state, &mut self.systems)
+    }
+}
+
 impl EventHandler for App {
 
 
     fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
+        self.check_gamepads(_ctx);
+        self.systems.mixer.update(timer::delta(_ctx).as_secs_f32());
+
+        let dt = 1.0 / UPDATES_PER_SECOND as f32;
+        let mut nb_steps = 0;
+        while timer::check_update_time(_ctx, UPDATES_PER_SECOND) {
+            if nb_steps == MAX_STEPS_PER_FRAME {
+                // too far behind, the game slows down instead of freezing.
+                continue;
+            }
+            nb_steps += 1;
 
-        let mut wc = level::WorldChange::default();
-        if let Some(state) = self.state.as_ref(){            
-            wc = self.world.update(_ctx, &state, &mut self.systems);
-        }
+            self.idle_time += dt;
+            if self.idle_time >= DEMO_IDLE_TIME {
+                self.start_demo(_ctx);
+            }
 
-        if let Some(state) = self.state.as_mut(){
-            state.score += wc.score as i32;
+            let wc = self.fixed_step(dt);
+            if wc.quit {
+                event::quit(_ctx);
+                break;
+            }
+            if wc.resume {
+                self.resume();
+            }
+            if wc.settings {
+                self.apply_settings(_ctx);
+                self.save_settings();
+            }
+            if let Some((player, control_idx, label)) = wc.rebind {
+                self.start_rebinding(player, control_idx, label);
+            }
+            if wc.restart {
+                if let (Some(state), Some(saved)) = (self.state.as_mut(), self.state_at_load.as_ref()){
+                    state.restore(saved);
+                }
+                self.load_level(self.current_level, _ctx);
+            }
+            if let Some(level_id) = wc.level{
+                // the demo leaves no scene but the one it started from.
+                if self.demo.is_some(){
+                    self.end_demo(_ctx);
+                    continue;
+                }
+                // leaving from the pause overlay abandons the run.
+                if let (Some(state), Some(_)) = (self.state.as_mut(), &self.pause){
+                    state.reset_run();
+                }
+                // a queued replay already picked the players.
+                if let (Some(players), Some(state), None) = (wc.players, self.state.as_mut(), &self.queued_replay){
+                    state.set_players(players);
+                }
+                self.load_level(level_id, _ctx);
+            }
         }
-            
-        if let Some(level_id) = wc.level{
-            self.world.stop();            
-            self.systems.renderer.clear();
-            let level = (*self.find_level(&level_id).unwrap()).clone();
-            let mut state = self.state.as_mut().unwrap();
-            self.world = level.load(&mut state, &mut self.systems, _ctx);
-            self.world.start(_ctx, state, &mut self.systems);
-        }
-           
         
         println!("FPS: {}", ggez::timer::fps(_ctx));
         return Ok(());
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let screen = self.systems.renderer.virtual_size;
+        self.world.relayout(&self.systems, &screen, ctx);
+        if let Some(overlay) = self.pause.as_mut(){
+            overlay.relayout(&self.systems, &screen, ctx);
+        }
 
-
-        let t = self.world.get_camera_actor().transform;        
+        let t = self.world.camera_draw_position();        
         self.systems.renderer.start_frame(ctx, t);
             
         self.systems.renderer.start_batch();
@@ -196,7 +785,7 @@ impl EventHandler for App {
         let mut draw_ctx = actors::DrawContext::WorldSpace;        
         self.systems.renderer.push_cam_transform(ctx);
         
-        for a in &mut self.world.actors {
+        for a in self.world.actors() {
             if !a.visible {
                 continue;
             }
@@ -224,7 +813,7 @@ impl EventHandler for App {
 
         let mut draw_ctx = actors::DrawContext::WorldSpace;              
         self.systems.renderer.pop_cam_transform(ctx);
-        for a in &mut self.world.actors {
+        for a in self.world.actors() {
             if !a.visible {
                 continue;
             }
@@ -246,108 +835,125 @@ impl EventHandler for App {
             // a.get_drawable().draw(a.transform, &mut self.systems.renderer, ctx);                        
         }    
 
+        if let Some(overlay) = self.pause.as_ref(){
+            // the overlay is screen space only, drawn over the dimmed world.
+            self.systems.renderer.pop_cam_transform(ctx);
+            self.systems.renderer.fill_screen(ctx, color::PAUSE_DIM);
+            for a in overlay.actors() {
+                if a.visible {
+                    self.systems.renderer_source.draw(a.get_drawable(), a.transform, ctx, &mut self.systems.renderer);
+                }
+            }
+        }
+
+        self.systems.renderer.pop_cam_transform(ctx);
+        self.systems.renderer.letterbox(ctx);
         self.systems.renderer.end_frame(ctx)
     }
 
 
     fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymod: KeyMods,  _repeat: bool) {
-        if let Some(p) = self.state.as_mut(){
-
-            match keycode {
-                KeyCode::Up    => { p.input.yaxis = 1.0;  }
-                KeyCode::Down  => { p.input.yaxis = -1.0; }
-                KeyCode::Left  => { p.input.xaxis = -1.0; }
-                KeyCode::Right => { p.input.xaxis = 1.0;  }
-                KeyCode::Space => { p.input.fire  = true; }
-                KeyCode::P => {
-                    let img = graphics::screenshot(ctx).expect("Could not take screenshot");
-                    img.encode(ctx, graphics::ImageFormat::Png, "/screenshot.png")
-                        .expect("Could not save screenshot");
-                }
-                KeyCode::Escape => event::quit(ctx),
-                _ => (), // Do nothing
-            }
+        if _repeat {
+            return;
         }
+        self.source_pressed(input::Device::Keyboard, input::Source::key(keycode), ctx);
     }
 
     fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymod: KeyMods) {
-        if let Some(p) = self.state.as_mut(){
-
-            match keycode {
-                KeyCode::Up    => { p.input.yaxis = 0.0;  }
-                KeyCode::Down  => { p.input.yaxis = 0.0; }
-                KeyCode::Left  => { p.input.xaxis = 0.0; }
-                KeyCode::Right => { p.input.xaxis = 0.0;  }
-                KeyCode::Space => { p.input.fire  = false; }                
-                _ => (), // Do nothing
-            }
-        }
+        self.source_released(input::Device::Keyboard, input::Source::key(keycode));
     }
 
     fn gamepad_axis_event(&mut self, _ctx: &mut Context, axis: Axis, _value: f32, _id: GamepadId ) {
-        
-        if let Some(p) = self.state.as_mut(){            
-            if axis == Axis::LeftStickX {
-                p.input.xaxis = _value;
-            }
-            if axis == Axis::LeftStickY {
-                p.input.yaxis = _value;                
+        self.gamepad_seen(_id, _ctx);
+        let device = input::Device::Gamepad(_id);
+        if _value.abs() >= input::PRESS_THRESHOLD {
+            self.device_used(device);
+            if self.demo.is_some() {
+                self.end_demo(_ctx);
             }
-
         }
+        self.mapper.move_axis(device, input::Source::axis(axis), _value);
+        self.update_input();
+    }
+
+    fn gamepad_button_down_event(&mut self, _ctx: &mut Context, _btn: Button, _id: GamepadId) {
+        self.gamepad_seen(_id, _ctx);
+        self.source_pressed(input::Device::Gamepad(_id), input::Source::button(_btn), _ctx);
     }
 
     fn gamepad_button_up_event(&mut self, _ctx: &mut Context, _btn: Button, _id: GamepadId) {        
-        if let Some(state) = self.state.as_mut(){            
-            // if _btn == Button::LeftTrigger {
-            //     p.input.lastButtonUp = Btn;
-            // }
-            state.paused = !state.paused
+        self.source_released(input::Device::Gamepad(_id), input::Source::button(_btn));
+    }
+
+    /// Leaving the window pauses the game, coming back leaves the overlay open.
+    fn focus_event(&mut self, ctx: &mut Context, gained: bool) {
+        if !gained {
+            self.pause(ctx);
         }
     }
 
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        self.systems.renderer.resize(ctx, width, height);
+    }
+
+    fn quit_event(&mut self, ctx: &mut Context) -> bool {
+        self.save_recording(ctx);
+        false
+    }
+
 }
 
 
+/// The scene graph, in the resources.
+const LEVELS_FILE : &str = "/levels.ron";
+
 fn connect_levels(app : &mut App, ctx: &mut Context){
-    let mut intro    = level::Level::new("Intro".to_string());
-    let mut tutorial = level::Level::new("tuto".to_string());
-    let mut play     = level::Level::new("play".to_string());
-    let mut gameover = level::Level::new("gameover".to_string());
-    let mut victory  = level::Level::new("victory".to_string());
-    let ref next_str = "next".to_string();
-    intro.add_transition(next_str, &tutorial);
-    tutorial.add_transition(next_str, &play);
-    play.add_transition(&"win".to_string(), &victory);
-    play.add_transition(&"lose".to_string(), &gameover);
-    victory.add_transition(next_str, &intro);
-    gameover.add_transition(next_str, &intro);
-    //
-    intro.loader    = level::introload;
-    tutorial.loader = level::tutoload;
-    gameover.loader = level::gameoverload;
-    victory.loader  = level::victoryload;
-    play.loader     = level::playload;
-    app.levels.push(intro);
-    app.levels.push(tutorial);
-    app.levels.push(gameover);
-    app.levels.push(victory);
-    app.levels.push(play);
+    let mut graph_str = String::new();
+    filesystem::open(ctx, LEVELS_FILE)
+        .and_then(|mut f| f.read_to_string(&mut graph_str).map_err(|e| e.into()))
+        .expect("Could not read the level graph");
+    app.levels = level_graph::load_levels(&graph_str)
+        .unwrap_or_else(|e| panic!("Invalid level graph {}:\n{}", LEVELS_FILE, e));
     
-    let mut state = app.state.as_mut().unwrap();
-    let mut w = app.levels[0].load(&mut state, &mut app.systems, ctx);
+    let start_id = app.levels[0].id;
+    app.load_level(start_id, ctx);
+}
+
 
-    if let Some(state) = app.state.as_ref(){
-        // let input = &app.player.as_ref().unwrap().input.clone();
 
-        w.start(ctx, state,  &mut app.systems);
-        app.world = w;
+/// The demo scripted in the resources, the attract mode is off without it.
+fn load_demo(ctx : &mut Context) -> Option<replay::Replay> {
+    let mut demo_str = String::new();
+    filesystem::open(ctx, DEMO_FILE).ok()?.read_to_string(&mut demo_str).ok()?;
+    match replay::Script::from_ron(&demo_str) {
+        Ok(script) => Some(script.to_replay(1.0 / UPDATES_PER_SECOND as f32)),
+        Err(e) => {
+            println!("Could not load demo {}: {}", DEMO_FILE, e);
+            None
+        }
     }
+}
 
-    
+/// `--replay <file>` plays a recorded run back instead of reading the player input.
+fn replay_arg() -> Option<path::PathBuf> {
+    let args : Vec::<String> = env::args().collect();
+    let idx = args.iter().position(|a| a == "--replay")?;
+    args.get(idx + 1).map(path::PathBuf::from)
 }
 
+/// `--export-sounds <dir>` writes the sounds of the first level of the run as WAV files, then quits.
+fn export_sounds_arg() -> Option<path::PathBuf> {
+    let args : Vec::<String> = env::args().collect();
+    let idx = args.iter().position(|a| a == "--export-sounds")?;
+    args.get(idx + 1).map(path::PathBuf::from)
+}
 
+/// The settings file, in the user config dir ggez uses.
+/// It is read before the window opens, so it can not ask ggez for it.
+fn settings_path() -> Option<path::PathBuf> {
+    let dirs = directories::ProjectDirs::from("", GAME_AUTHOR, GAME_ID)?;
+    Some(dirs.config_dir().join(settings::SETTINGS_FILE))
+}
 
 fn main() {
     // We add the CARGO_MANIFEST_DIR/resources to the resource paths
@@ -360,20 +966,40 @@ fn main() {
         path::PathBuf::from("./resources")
     };
 
-    let window_setup = conf::WindowSetup::default().title("Dead Pulsar");
-    let window_mode  = conf::WindowMode::default().dimensions( 1280.0, 720.0);
+    let settings = match settings_path() {
+        Some(path) => settings::Settings::load(path),
+        None => {
+            println!("No user config dir, the settings will not be saved.");
+            settings::Settings::default()
+        }
+    };
 
-    let screen = Size{x: window_mode.width, y :window_mode.height};
+    let screen = VIRTUAL_SCREEN;
+
+    if let Some(dir) = export_sounds_arg() {
+        let state = GameState::new(screen, initial_seed());
+        let sounds = synth::LevelSounds::new(state.level_sound_seed());
+        match sounds.export(&dir) {
+            Ok(_)  => println!("Sounds of seed {} written to {}", state.seed, dir.display()),
+            Err(e) => println!("Could not write sounds: {}", e)
+        }
+        return;
+    }
 
     // Make a Context and an EventLoop.
-    let (mut ctx, mut event_loop) = ContextBuilder::new("dead pulsar", "LBdN")
+    let (mut ctx, mut event_loop) = ContextBuilder::new(GAME_ID, GAME_AUTHOR)
            .add_resource_path(resource_dir)
-           .window_setup(window_setup)
-           .window_mode(window_mode)
+           .window_setup(settings.window_setup(GAME_TITLE))
+           .window_mode(settings.window_mode())
            .build()
            .unwrap();
 
-    let mut app = App::new(&mut ctx, screen);
+    let mut app = App::new(&mut ctx, screen, settings);
+
+    if let Some(replay_path) = replay_arg() {
+        let replay = replay::Replay::load(&replay_path).expect("Could not load replay");
+        app.queue_replay(replay);
+    }
 
     connect_levels(&mut app, &mut ctx);
           
@@ -0,0 +1,163 @@
+use crate::render;
+use crate::settings;
+use crate::text;
+use crate::unit::*;
+use crate::InputState;
+
+/// A discrete press, out of the held input.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MenuEvent{
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Back,
+}
+
+/// Turns the held input into presses : a direction repeats while held,
+/// confirm and back only count once released then pressed again,
+/// they may still be held from the previous scene.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MenuInput{
+    /// Seconds before a held direction repeats.
+    repeat     : f32,
+    fire_armed : bool,
+    back_armed : bool,
+}
+
+impl MenuInput{
+    const REPEAT_DELAY : f32 = 0.2;
+
+    pub fn new() -> Self{
+        MenuInput{
+            repeat     : 0.0,
+            fire_armed : false,
+            back_armed : false,
+        }
+    }
+
+    pub fn update(&mut self, input : &InputState, dt : f32) -> Option<MenuEvent>{
+        if !input.fire && !input.confirm {
+            self.fire_armed = true;
+        } else if self.fire_armed {
+            self.fire_armed = false;
+            return Some(MenuEvent::Confirm);
+        }
+        if !input.back {
+            self.back_armed = true;
+        } else if self.back_armed {
+            self.back_armed = false;
+            return Some(MenuEvent::Back);
+        }
+
+        if input.xaxis.abs() < 0.5 && input.yaxis.abs() < 0.5 {
+            self.repeat = 0.0;
+            return None;
+        }
+        self.repeat -= dt;
+        if self.repeat > 0.0 {
+            return None;
+        }
+        self.repeat = MenuInput::REPEAT_DELAY;
+
+        if input.yaxis >= 0.5 {
+            Some(MenuEvent::Up)
+        } else if input.yaxis <= -0.5 {
+            Some(MenuEvent::Down)
+        } else if input.xaxis >= 0.5 {
+            Some(MenuEvent::Right)
+        } else {
+            Some(MenuEvent::Left)
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MenuAction{
+    /// Leaves for the scene with this id.
+    Goto(Id),
+    /// Starts a new run for `players` players, in the scene with this id.
+    NewRun{ next_scene_idx : Id, players : usize },
+    Quit,
+    /// Closes the pause overlay.
+    Resume,
+    /// Loads the paused level again.
+    Restart,
+    /// An item showing a setting, left and right change it, confirm moves it forward.
+    Setting(settings::Setting),
+    /// Returned for a setting item, `step` notches forward or back.
+    Change{ setting : settings::Setting, step : i32 },
+    /// An item showing a control of `input::CONTROLS` for the player `player`,
+    /// confirm waits for its new key or button.
+    Rebind{ player : usize, control : usize },
+}
+
+/// A vertical list of text items, the selected one highlighted.
+/// The labels are text models drawn by their own actors, the menu restyles them.
+/// Menus live in `Systems`, the `Effect::Menu` of an actor runs one.
+pub struct Menu{
+    items    : Vec::<(Id, MenuAction)>,
+    selected : usize,
+    input    : MenuInput,
+    /// Item picked by back, wherever the selection is.
+    back     : Option<usize>,
+}
+
+impl Menu{
+    pub fn new() -> Self{
+        Menu{
+            items    : Vec::<(Id, MenuAction)>::new(),
+            selected : 0,
+            input    : MenuInput::new(),
+            back     : None,
+        }
+    }
+
+    /// `label` is the text model of the item.
+    pub fn push(&mut self, label : Id, action : MenuAction){
+        self.items.push((label, action));
+    }
+
+    pub fn set_back(&mut self, idx : usize){
+        self.back = Some(idx);
+    }
+
+    /// Style of the item at `idx`.
+    pub fn item_style(&self, idx : usize) -> text::FontStyle{
+        if idx == self.selected { text::menu_selected_style() } else { text::menu_style() }
+    }
+
+    /// Text model of the item selected.
+    pub fn selected_label(&self) -> Id{
+        self.items[self.selected].0
+    }
+
+    /// Moves the selection, returns the action of the item confirmed if any.
+    pub fn update(&mut self, input : &InputState, dt : f32, renderer_source : &mut render::RendererSource) -> Option<MenuAction>{
+        let count = self.items.len();
+        if count == 0 {
+            return None;
+        }
+        let last_selected = self.selected;
+        let action = self.items[self.selected].1;
+        match (self.input.update(input, dt), action){
+            (Some(MenuEvent::Up), _)   => self.selected = (self.selected + count - 1) % count,
+            (Some(MenuEvent::Down), _) => self.selected = (self.selected + 1) % count,
+            (Some(MenuEvent::Left), MenuAction::Setting(setting))    => return Some(MenuAction::Change{ setting, step : -1 }),
+            (Some(MenuEvent::Right), MenuAction::Setting(setting))
+            | (Some(MenuEvent::Confirm), MenuAction::Setting(setting)) => return Some(MenuAction::Change{ setting, step : 1 }),
+            (Some(MenuEvent::Confirm), _) => return Some(action),
+            (Some(MenuEvent::Back), _) => return self.back.map(|idx| self.items[idx].1),
+            _ => ()
+        }
+        if self.selected != last_selected {
+            for idx in [last_selected, self.selected].iter(){
+                if let Some(tm) = renderer_source.textmodels.get_mut(&self.items[*idx].0){
+                    tm.set_style(self.item_style(*idx));
+                }
+            }
+        }
+        None
+    }
+}
@@ -5,7 +5,6 @@ use std::f64::consts::{PI, FRAC_PI_2};
 use nalgebra as nal;
 use nal::{Vector3, Rotation3};
 use rand::Rng;
-use rand::rngs::ThreadRng;
 
 use crate::unit::*;
 
@@ -20,7 +19,7 @@ pub fn regular_polygon(dist : f32, nb_side: i32) -> Vec::<Position> {
     result
 }
 
-pub fn irregular_polygon(dist_range: &Bounds1D<f32>, nb_side: i32, rng : &mut ThreadRng) -> Vec::<Position> {
+pub fn irregular_polygon(dist_range: &Bounds1D<f32>, nb_side: i32, rng : &mut GameRng) -> Vec::<Position> {
     // TODO : use irregular angles.
     // see https://stackoverflow.com/questions/50405397/split-number-into-4-random-numbers
     let mut result = Vec::<Position>::new();    
@@ -34,7 +33,7 @@ pub fn irregular_polygon(dist_range: &Bounds1D<f32>, nb_side: i32, rng : &mut Th
     result
 }
 
-pub fn bump(normal : &Vector2, dist_range: &Bounds1D<f32>, nb_side : i32, rng : &mut ThreadRng) -> Vec::<Position> {
+pub fn bump(normal : &Vector2, dist_range: &Bounds1D<f32>, nb_side : i32, rng : &mut GameRng) -> Vec::<Position> {
     let mut result = Vec::<Position>::new();  
 
     let start_angle = normal.x.acos() as f64 - FRAC_PI_2 ;
@@ -51,7 +50,7 @@ pub fn bump(normal : &Vector2, dist_range: &Bounds1D<f32>, nb_side : i32, rng :
     result
 }
 
-pub fn bump2(normal : &Vector2, nb_side : i32, dist_range: &Bounds1D<f32>, rng : &mut ThreadRng) -> (Vec::<Position>, Vec<f32>) {
+pub fn bump2(normal : &Vector2, nb_side : i32, dist_range: &Bounds1D<f32>, rng : &mut GameRng) -> (Vec::<Position>, Vec<f32>) {
     let mut result = Vec::<Position>::new();          
     let mut xpos = Vec::<f32>::new();
     for i in 0..=nb_side{
@@ -65,7 +64,7 @@ pub fn bump2(normal : &Vector2, nb_side : i32, dist_range: &Bounds1D<f32>, rng :
     (result, xpos)
 }
 
-pub fn bump3(nb_side : i32, dist_range: &Bounds1D<f32>, rng : &mut ThreadRng) -> (Vec::<Position>, Vec<f32>) {
+pub fn bump3(nb_side : i32, dist_range: &Bounds1D<f32>, rng : &mut GameRng) -> (Vec::<Position>, Vec<f32>) {
     let normal = Vector2::new(0.0, 1.0);
     let mut result = Vec::<Position>::new();          
     let mut xpos = Vec::<f32>::new();
@@ -81,7 +80,7 @@ pub fn bump3(nb_side : i32, dist_range: &Bounds1D<f32>, rng : &mut ThreadRng) ->
 }
 
 
-pub fn crystal_polygon(dist_range: Bounds1D<f32>, nb_side: i32, rng : &mut ThreadRng) -> Vec::<Position> {
+pub fn crystal_polygon(dist_range: Bounds1D<f32>, nb_side: i32, rng : &mut GameRng) -> Vec::<Position> {
     let mut result = Vec::<Position>::new();    
 
     let start_angle = rng.gen_range(PI * 0.05, PI * 0.45);
@@ -148,6 +147,15 @@ pub fn base_ship(dist: f32) -> Vec::<Position> {
     result
 }
 
+pub fn projectile(dist: f32) -> Vec::<Position> {
+    vec![
+        Position{x:  2.0f32 * dist, y:  0.0f32},
+        Position{x:  0.0f32,        y:  0.5f32 * dist},
+        Position{x: -dist,          y:  0.0f32},
+        Position{x:  0.0f32,        y: -0.5f32 * dist},
+    ]
+}
+
 pub fn cockpit_ship(dist: f32) -> Vec::<Position> {
     let mut result = Vec::<Position>::new();
     result.push( Position{x:  3.0f32 * dist, y: 0.0f32 * dist});
@@ -6,6 +6,14 @@ use crate::unit::*;
 use crate::text;
 use std::collections::HashMap;
 
+/// Models are drawn around their origin, rotated then scaled by the actor transform.
+fn transform_param(transform : &Transform) -> DrawParam{
+    DrawParam::default()
+        .dest(transform.position)
+        .rotation(transform.rotation)
+        .scale(Vector2::new(transform.scale, transform.scale))
+}
+
 pub struct MeshModel{
     pub polygons : Vec::<MeshModelPoly>,
     mesh_oidx: Option<usize>,
@@ -21,7 +29,7 @@ impl MeshModel{
         }
     }
 
-    pub fn draw(&mut self, transform : super::unit::Position, renderer : &mut Renderer, ctx : &mut Context){        
+    pub fn draw(&mut self, transform : Transform, renderer : &mut Renderer, ctx : &mut Context){        
         if self.polygons.len() == 0 {
             return;
         }
@@ -43,7 +51,7 @@ impl MeshModel{
             self.dirty = false;  
         } 
         if let Some(mesh_idx) = self.mesh_oidx{
-            let _ = renderer.meshes[mesh_idx].draw(ctx, DrawParam::default().dest(transform));
+            let _ = renderer.meshes[mesh_idx].draw(ctx, transform_param(&transform));
         }        
     }
 
@@ -82,6 +90,8 @@ pub struct TextModel{
     pub string: String,
     fontstyle : text::FontStyle, 
     text_anchor : TextAnchor,
+    /// Offset of the anchor, measured when the text is rebuilt.
+    anchor_offset : Position,
     text_oidx:Option<usize>, 
     pub dirty:bool,    
 }
@@ -94,20 +104,20 @@ impl TextModel{
             string: string,
             fontstyle : fontstyle, 
             text_anchor : text_anchor,
+            anchor_offset : Position{ x : 0.0, y : 0.0 },
             text_oidx:None, 
             dirty:true,    
         }
     }
 
-    pub fn draw(&mut self, transform : super::unit::Position, renderer : &mut Renderer, ctx : &mut Context){        
-        let mut t =  transform.clone();
+    pub fn draw(&mut self, transform : Transform, renderer : &mut Renderer, ctx : &mut Context){        
         if self.dirty {
             let font = renderer.fonts[&self.fontstyle.name];
             let text = graphics::Text::new( (self.string.clone() , font, self.fontstyle.size) );
             
             if let TextAnchor::Center = self.text_anchor {
-                t.x -= text.width(ctx) as f32 / 2.0;
-                t.y -= text.height(ctx) as f32;
+                self.anchor_offset.x = -(text.width(ctx) as f32) / 2.0;
+                self.anchor_offset.y = -(text.height(ctx) as f32);
             }
 
 
@@ -119,13 +129,17 @@ impl TextModel{
             }
             self.dirty = false;  
         } 
+        let mut t = transform.position;
+        t.x += self.anchor_offset.x;
+        t.y += self.anchor_offset.y;
         if let Some(text_idx) = self.text_oidx{            
-            let _ = &renderer.texts[text_idx].draw(ctx, DrawParam::default().dest(t).color(self.fontstyle.color));
+            let param = transform_param(&Transform{ position : t, ..transform }).color(self.fontstyle.color);
+            let _ = &renderer.texts[text_idx].draw(ctx, param);
         }        
     }
 
     pub fn get_screen_size(&self, renderer : &Renderer, ctx : &mut Context) -> (u32, u32) {
-        if let Some(text_idx) = self.text_oidx{
+        if let (false, Some(text_idx)) = (self.dirty, self.text_oidx){
             return renderer.texts[text_idx].dimensions(ctx);
         } else {
             let font = renderer.fonts[&self.fontstyle.name];
@@ -138,6 +152,11 @@ impl TextModel{
         self.string = newstr;
         self.dirty = true;
     }
+
+    pub fn set_style(&mut self, fontstyle : text::FontStyle) {
+        self.fontstyle = fontstyle;
+        self.dirty = true;
+    }
 }
 
 pub struct RendererSource{
@@ -169,7 +188,7 @@ impl RendererSource{
         self.textmodels.get(&id)
     }
 
-    pub fn draw(&mut self, id : Id, transform : Position, ctx : &mut Context, renderer : &mut Renderer) {
+    pub fn draw(&mut self, id : Id, transform : Transform, ctx : &mut Context, renderer : &mut Renderer) {
         if let Some(mm)= self.meshmodels.get_mut(&id){
             mm.draw(transform, renderer, ctx);
         }
@@ -189,6 +208,8 @@ pub struct Renderer{
     pub mb     : graphics::MeshBuilder,
     pub meshes      : Vec::<Mesh>,    
     pub texts       : Vec::<graphics::Text>,
+    /// Size of the screen the game draws on, whatever the window size.
+    pub virtual_size : Size,
     cam_tr          : Position
 }
 
@@ -199,6 +220,7 @@ impl Renderer{
             mb         : graphics::MeshBuilder::new(),                  
             meshes     : Vec::<Mesh>::new(),            
             texts      : Vec::<graphics::Text>::new(),
+            virtual_size : Size{x: 0.0, y: 0.0},
             cam_tr     : super::unit::Position{x: 0.0, y:0.0}
         }
     }
@@ -216,11 +238,12 @@ impl Renderer{
     }
 
     pub fn push_cam_transform(&mut self, ctx: &mut Context){
-        let wh = graphics::screen_coordinates(ctx).h;
+        // y goes up in the world, the flip pivots around the middle of the screen.
+        let wh = self.virtual_size.y;
         let cam_transform = DrawParam::default()
-                                .dest(self.cam_tr)
+                                .dest(Point2::new(self.cam_tr.x, -self.cam_tr.y))
                                 .scale(Vector2::new(1.0, -1.0))
-                                .offset(Point2::new(0.0, 360.0))
+                                .offset(Point2::new(0.0, wh / 2.0))
                                 .to_matrix();                
         graphics::push_transform(ctx, Some(cam_transform));
         graphics::apply_transformations(ctx).unwrap();
@@ -239,6 +262,44 @@ impl Renderer{
         mesh.draw(ctx, DrawParam::default().dest([0.0,0.0])).unwrap();
     }
 
+    /// Fits the virtual screen in a `width` x `height` window, as large as it goes
+    /// without distortion and centered.
+    pub fn resize(&mut self, ctx: &mut Context, width : f32, height : f32){
+        let v = self.virtual_size;
+        let scale = (width / v.x).min(height / v.y);
+        let (w, h) = (width / scale, height / scale);
+        let rect = Rect{ x : (v.x - w) / 2.0, y : (v.y - h) / 2.0, w, h };
+        graphics::set_screen_coordinates(ctx, rect).unwrap();
+    }
+
+    /// Hides what was drawn around the virtual screen behind black bars.
+    pub fn letterbox(&self, ctx: &mut Context){
+        let r = graphics::screen_coordinates(ctx);
+        let v = self.virtual_size;
+        let bars = [
+            Rect{ x : r.x, y : r.y, w : -r.x, h : r.h },
+            Rect{ x : v.x, y : r.y, w : r.x + r.w - v.x, h : r.h },
+            Rect{ x : r.x, y : r.y, w : r.w, h : -r.y },
+            Rect{ x : r.x, y : v.y, w : r.w, h : r.y + r.h - v.y },
+        ];
+        let mut mb = graphics::MeshBuilder::new();
+        let mut empty = true;
+        for bar in bars.iter().filter(|b| b.w > 0.0 && b.h > 0.0){
+            mb.rectangle(DrawMode::fill(), *bar, graphics::BLACK);
+            empty = false;
+        }
+        if !empty {
+            let mesh = mb.build(ctx).unwrap();
+            mesh.draw(ctx, DrawParam::default()).unwrap();
+        }
+    }
+
+    /// Covers the whole screen with `color`, translucent colors dim what is already drawn.
+    pub fn fill_screen(&self, ctx: &mut Context, color : Color){
+        let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), graphics::screen_coordinates(ctx), color).unwrap();
+        mesh.draw(ctx, DrawParam::default()).unwrap();
+    }
+
     pub fn end_frame(&self, ctx: &mut Context) -> GameResult<()>{
         return graphics::present(ctx);
     }   
@@ -0,0 +1,182 @@
+use std::fs;
+use std::io;
+use std::iter;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::InputState;
+use crate::flight::FlightModel;
+
+/// One simulation step : the time it lasted and the input that drove it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayFrame{
+    pub dt    : f32,
+    pub input : InputState,
+    /// The inputs of the other players, if any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub others : Vec::<InputState>,
+}
+
+impl ReplayFrame{
+    /// `inputs` holds one input per player, the first player first.
+    pub fn new(dt : f32, inputs : &[InputState]) -> Self{
+        ReplayFrame{
+            dt,
+            input  : inputs.first().copied().unwrap_or_default(),
+            others : inputs.iter().skip(1).copied().collect(),
+        }
+    }
+
+    pub fn inputs(&self) -> Vec::<InputState>{
+        iter::once(self.input).chain(self.others.iter().copied()).collect()
+    }
+}
+
+fn one_player() -> usize{
+    1
+}
+
+/// A recorded run of a single world.
+/// The seed, level and players are enough to regenerate the world,
+/// the flight models and frames are enough to replay what happened in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay{
+    pub seed   : u64,
+    pub level  : i32,
+    #[serde(default = "one_player")]
+    pub players : usize,
+    /// One per player, the ones missing fly with the flight model of the settings.
+    #[serde(default)]
+    pub flight_models : Vec::<FlightModel>,
+    pub frames : Vec::<ReplayFrame>,
+}
+
+impl Replay{
+    /// A replay for as many players as `flight_models`.
+    pub fn new(seed : u64, level : i32, flight_models : Vec::<FlightModel>) -> Self{
+        Replay{
+            seed,
+            level,
+            players : flight_models.len(),
+            flight_models,
+            frames : Vec::<ReplayFrame>::new(),
+        }
+    }
+
+    pub fn to_ron(&self) -> String{
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap()
+    }
+
+    pub fn from_ron(s : &str) -> io::Result<Replay>{
+        ron::de::from_str(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    pub fn save(&self, path : &Path) -> io::Result<()>{
+        if let Some(dir) = path.parent(){
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_ron())
+    }
+
+    pub fn load(path : &Path) -> io::Result<Replay>{
+        Replay::from_ron(&fs::read_to_string(path)?)
+    }
+}
+
+/// An input held for `time` seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeldInput{
+    pub time  : f32,
+    pub input : InputState,
+}
+
+/// A run written by hand for a single player flying the default flight model :
+/// the seed and level to play, then the inputs one after the other.
+/// It stays short enough to read, and does not depend on the update rate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Script{
+    pub seed   : u64,
+    pub level  : i32,
+    pub inputs : Vec::<HeldInput>,
+}
+
+impl Script{
+    pub fn from_ron(s : &str) -> io::Result<Script>{
+        ron::de::from_str(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    /// The replay of the script, in steps of `dt` seconds.
+    pub fn to_replay(&self, dt : f32) -> Replay{
+        let mut replay = Replay::new(self.seed, self.level, vec![FlightModel::default()]);
+        for held in &self.inputs {
+            let count = (held.time / dt).round() as usize;
+            replay.frames.extend(iter::repeat_n(ReplayFrame::new(dt, &[held.input]), count));
+        }
+        replay
+    }
+}
+
+/// Accumulates the frames of the world being played.
+pub struct Recorder{
+    replay : Replay,
+}
+
+impl Recorder{
+    pub fn new(seed : u64, level : i32, flight_models : Vec::<FlightModel>) -> Self{
+        Recorder{
+            replay : Replay::new(seed, level, flight_models)
+        }
+    }
+
+    pub fn record(&mut self, frame : &ReplayFrame){
+        self.replay.frames.push(frame.clone());
+    }
+
+    pub fn finish(self) -> Replay{
+        self.replay
+    }
+}
+
+/// Feeds the frames of a replay back, one per update.
+pub struct Playback{
+    replay : Replay,
+    cursor : usize,
+}
+
+impl Playback{
+    pub fn new(replay : Replay) -> Self{
+        Playback{
+            replay,
+            cursor : 0
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Option<ReplayFrame>{
+        let frame = self.replay.frames.get(self.cursor).cloned();
+        self.cursor += 1;
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn script_holds_each_input(){
+        let up = InputState{ yaxis : -1.0, ..InputState::default() };
+        let script = Script{
+            seed   : 3,
+            level  : 1,
+            inputs : vec![
+                HeldInput{ time : 0.5, input : InputState::default() },
+                HeldInput{ time : 0.25, input : up },
+            ],
+        };
+        let replay = script.to_replay(0.125);
+        assert_eq!((replay.seed, replay.level, replay.players), (3, 1, 1));
+        let yaxes : Vec::<f32> = replay.frames.iter().map(|f| f.input.yaxis).collect();
+        assert_eq!(yaxes, vec![0.0, 0.0, 0.0, 0.0, -1.0, -1.0]);
+    }
+}
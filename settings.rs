@@ -0,0 +1,257 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use ggez::conf;
+use serde::{Serialize, Deserialize};
+
+use crate::flight;
+use crate::input;
+use crate::MIN_WINDOW;
+
+/// The settings, in the user config dir.
+pub const SETTINGS_FILE : &str = "settings.ron";
+
+/// Window sizes the settings scene cycles through.
+const RESOLUTIONS   : &[(u32, u32)] = &[(960, 540), (1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+const VOLUME_STEP   : f32 = 0.1;
+const DEADZONE_STEP : f32 = 0.05;
+
+/// A line of the settings scene.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Setting{
+    Resolution,
+    Fullscreen,
+    Vsync,
+    MasterVolume,
+    SfxVolume,
+    MusicVolume,
+    GamepadDeadzone,
+    InvertStickY,
+    FlightModel,
+}
+
+impl Setting{
+    /// In the order the settings scene lists them.
+    pub const ALL : [Setting; 9] = [
+        Setting::Resolution,
+        Setting::Fullscreen,
+        Setting::Vsync,
+        Setting::MasterVolume,
+        Setting::SfxVolume,
+        Setting::MusicVolume,
+        Setting::GamepadDeadzone,
+        Setting::InvertStickY,
+        Setting::FlightModel,
+    ];
+}
+
+/// Video, audio and control settings.
+/// Missing fields take their default, so older files still load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings{
+    /// Size of the window when it is not fullscreen.
+    pub resolution       : (u32, u32),
+    pub fullscreen       : bool,
+    /// Only read when the window opens.
+    pub vsync            : bool,
+    /// Volumes go from 0 to 1, the master one scales the others.
+    pub master_volume    : f32,
+    pub sfx_volume       : f32,
+    pub music_volume     : f32,
+    pub input            : input::InputMap,
+    /// Bindings of the second player, sharing the keyboard with the first one.
+    pub second_input     : input::InputMap,
+    /// Flight model of the levels that do not pick one.
+    pub flight_model     : flight::FlightModel,
+    /// Where `save` writes, headless runs have none.
+    #[serde(skip)]
+    path                 : Option<PathBuf>,
+}
+
+impl Default for Settings{
+    fn default() -> Self{
+        Settings{
+            resolution       : (1280, 720),
+            fullscreen       : false,
+            vsync            : true,
+            master_volume    : 1.0,
+            sfx_volume       : 1.0,
+            music_volume     : 0.7,
+            input            : input::InputMap::default(),
+            second_input     : input::InputMap::second_player(),
+            flight_model     : flight::FlightModel::default(),
+            path             : None,
+        }
+    }
+}
+
+fn percent(volume : f32) -> String{
+    format!("{}%", (volume * 100.0).round() as i32)
+}
+
+fn on_off(value : bool) -> &'static str{
+    if value { "On" } else { "Off" }
+}
+
+/// `value` moved by `step` times `increment`, kept in `min..=max`.
+fn nudge(value : f32, step : i32, increment : f32, min : f32, max : f32) -> f32{
+    // rounded so repeated steps do not drift away from the grid.
+    let stepped = ((value + step as f32 * increment) / increment).round() * increment;
+    stepped.clamp(min, max)
+}
+
+impl Settings{
+    /// A missing file gives the defaults, saved to `path` later on.
+    /// An invalid one is reported and replaced by the defaults too.
+    pub fn load(path : PathBuf) -> Self{
+        let mut settings = match fs::read_to_string(&path){
+            Ok(s) => match ron::de::from_str::<Settings>(&s){
+                Ok(settings) => settings,
+                Err(e) => {
+                    println!("Invalid settings file {}: {}", path.display(), e);
+                    Settings::default()
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                println!("Could not read settings file {}: {}", path.display(), e);
+                Settings::default()
+            }
+        };
+        for problem in settings.validate(){
+            println!("Settings file {}: {}", path.display(), problem);
+        }
+        settings.path = Some(path);
+        settings
+    }
+
+    /// Brings every value back in its range, returns what had to be fixed.
+    pub fn validate(&mut self) -> Vec::<String>{
+        let mut problems = Vec::<String>::new();
+
+        let (width, height) = self.resolution;
+        let min_width  = MIN_WINDOW.x as u32;
+        let min_height = MIN_WINDOW.y as u32;
+        if width < min_width || height < min_height {
+            problems.push(format!("resolution {}x{} is too small", width, height));
+            self.resolution = (width.max(min_width), height.max(min_height));
+        }
+
+        let volumes = [
+            ("master_volume", &mut self.master_volume),
+            ("sfx_volume", &mut self.sfx_volume),
+            ("music_volume", &mut self.music_volume),
+        ];
+        for (name, volume) in volumes {
+            if !(0.0..=1.0).contains(volume) {
+                problems.push(format!("{} {} is not between 0 and 1", name, volume));
+                *volume = if volume.is_nan() { 1.0 } else { volume.clamp(0.0, 1.0) };
+            }
+        }
+
+        self.input.validate(&input::InputMap::default(), &mut problems);
+        self.second_input.validate(&input::InputMap::second_player(), &mut problems);
+        problems
+    }
+
+    pub fn save(&self) -> io::Result<()>{
+        let path = match &self.path{
+            Some(path) => path,
+            None       => return Ok(())
+        };
+        if let Some(dir) = path.parent(){
+            fs::create_dir_all(dir)?;
+        }
+        let s = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap();
+        fs::write(path, s)
+    }
+
+    pub fn window_setup(&self, title : &str) -> conf::WindowSetup{
+        conf::WindowSetup::default()
+            .title(title)
+            .vsync(self.vsync)
+    }
+
+    pub fn window_mode(&self) -> conf::WindowMode{
+        let fullscreen_type = if self.fullscreen { conf::FullscreenType::Desktop } else { conf::FullscreenType::Windowed };
+        conf::WindowMode::default()
+            .dimensions(self.resolution.0 as f32, self.resolution.1 as f32)
+            .min_dimensions(MIN_WINDOW.x, MIN_WINDOW.y)
+            .resizable(true)
+            .fullscreen_type(fullscreen_type)
+    }
+
+    /// The bindings of the player `player`, 0 for the first.
+    pub fn player_input(&self, player : usize) -> &input::InputMap{
+        if player == 0 { &self.input } else { &self.second_input }
+    }
+
+    pub fn player_input_mut(&mut self, player : usize) -> &mut input::InputMap{
+        if player == 0 { &mut self.input } else { &mut self.second_input }
+    }
+
+    pub fn sfx_gain(&self) -> f32{
+        self.master_volume * self.sfx_volume
+    }
+
+    pub fn music_gain(&self) -> f32{
+        self.master_volume * self.music_volume
+    }
+
+    /// Changes a setting by `step` notches, lists and switches wrap around.
+    pub fn adjust(&mut self, setting : Setting, step : i32){
+        match setting {
+            Setting::Resolution => {
+                let count = RESOLUTIONS.len() as i32;
+                let current = RESOLUTIONS.iter().position(|r| *r == self.resolution).map(|i| i as i32).unwrap_or(-1);
+                let next = if current < 0 && step < 0 { count - 1 } else { (current + step).rem_euclid(count) };
+                self.resolution = RESOLUTIONS[next as usize];
+            },
+            Setting::Fullscreen => self.fullscreen = !self.fullscreen,
+            Setting::Vsync      => self.vsync = !self.vsync,
+            Setting::MasterVolume => self.master_volume = nudge(self.master_volume, step, VOLUME_STEP, 0.0, 1.0),
+            Setting::SfxVolume    => self.sfx_volume    = nudge(self.sfx_volume, step, VOLUME_STEP, 0.0, 1.0),
+            Setting::MusicVolume  => self.music_volume  = nudge(self.music_volume, step, VOLUME_STEP, 0.0, 1.0),
+            Setting::GamepadDeadzone => {
+                // both axes of the stick share it.
+                let deadzone = nudge(self.input.deadzone(input::Action::MoveX), step, DEADZONE_STEP, 0.0, input::MAX_DEADZONE);
+                for map in [&mut self.input, &mut self.second_input] {
+                    map.set_deadzone(input::Action::MoveX, deadzone);
+                    map.set_deadzone(input::Action::MoveY, deadzone);
+                }
+            },
+            Setting::InvertStickY => {
+                let inverted = self.input.stick_y_inverted();
+                for map in [&mut self.input, &mut self.second_input] {
+                    map.set_stick_y_inverted(!inverted);
+                }
+            },
+            Setting::FlightModel => {
+                self.flight_model = match self.flight_model {
+                    flight::FlightModel::Direct => flight::FlightModel::inertia(),
+                    flight::FlightModel::Inertia{..} => flight::FlightModel::Direct,
+                };
+            }
+        }
+    }
+
+    /// The line of the settings scene showing `setting`.
+    pub fn describe(&self, setting : Setting) -> String{
+        match setting {
+            Setting::Resolution      => format!("Resolution: {}x{}", self.resolution.0, self.resolution.1),
+            Setting::Fullscreen      => format!("Fullscreen: {}", on_off(self.fullscreen)),
+            Setting::Vsync           => format!("Vsync: {} (on restart)", on_off(self.vsync)),
+            Setting::MasterVolume    => format!("Master volume: {}", percent(self.master_volume)),
+            Setting::SfxVolume       => format!("Effects volume: {}", percent(self.sfx_volume)),
+            Setting::MusicVolume     => format!("Music volume: {}", percent(self.music_volume)),
+            Setting::GamepadDeadzone => format!("Stick deadzone: {}", percent(self.input.deadzone(input::Action::MoveX))),
+            Setting::InvertStickY    => format!("Invert stick Y: {}", on_off(self.input.stick_y_inverted())),
+            Setting::FlightModel     => match self.flight_model {
+                flight::FlightModel::Direct      => "Flight: Direct".to_string(),
+                flight::FlightModel::Inertia{..} => "Flight: Inertia".to_string(),
+            },
+        }
+    }
+}
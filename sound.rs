@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use ggez::Context;
+use ggez::audio::{self, SoundData, SoundSource};
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::synth;
+
+/// Seconds a new music track takes to replace the one playing.
+const CROSSFADE_TIME : f32 = 1.5;
+/// Pitch drift of the synthesized groups.
+const SYNTH_PITCH_JITTER : f32 = 0.05;
+
+/// Groups of sounds an effect can play, by name.
+/// The levels synthesize their own pickup, death and fire sounds.
+pub const CRASH_SOUND  : &str = "crash";
+pub const PICKUP_SOUND : &str = "pickup";
+pub const DEATH_SOUND  : &str = "death";
+pub const FIRE_SOUND   : &str = "fire";
+
+/// A named group : its variants, files of the resources, and how far the pitch may drift
+/// from one play to the next (0.1 plays between 0.9 and 1.1 times the pitch).
+struct GroupDesc{
+    name         : &'static str,
+    files        : &'static [&'static str],
+    pitch_jitter : f32,
+}
+
+const GROUPS : &[GroupDesc] = &[
+    GroupDesc{
+        name         : CRASH_SOUND,
+        files        : &["/Randomize3.wav", "/Randomize5.wav", "/Randomize6.wav", "/Randomize7.wav", "/Randomize8.wav", "/Randomize9.wav"],
+        pitch_jitter : 0.1,
+    },
+    GroupDesc{
+        name         : PICKUP_SOUND,
+        files        : &["/Powerup.wav"],
+        pitch_jitter : 0.05,
+    },
+];
+
+/// Variants of a sound, one picked at random each time it plays.
+struct SoundGroup{
+    variants     : Vec::<audio::Source>,
+    pitch_jitter : f32,
+}
+
+/// A looping music, `fade` goes from 0 to 1 as it comes in and back to 0 as it leaves.
+struct Track{
+    name   : String,
+    source : audio::Source,
+    fade   : f32,
+}
+
+/// Plays the sounds and the music. Sounds go through the effects bus, music through its own,
+/// the settings give the volume of each with the master volume applied.
+/// Without an audio device nothing is loaded and every call does nothing.
+pub struct Mixer{
+    groups      : Vec::<SoundGroup>,
+    group_names : HashMap::<String, usize>,
+    /// The synthesized tunes, by name.
+    tunes       : HashMap::<String, SoundData>,
+    /// The track playing, and the ones fading out.
+    music       : Option<Track>,
+    fading      : Vec::<Track>,
+    sfx_gain    : f32,
+    music_gain  : f32,
+}
+
+impl Mixer{
+    pub fn new() -> Self{
+        Mixer{
+            groups      : Vec::<SoundGroup>::new(),
+            group_names : HashMap::<String, usize>::new(),
+            tunes       : HashMap::<String, SoundData>::new(),
+            music       : None,
+            fading      : Vec::<Track>::new(),
+            sfx_gain    : 1.0,
+            music_gain  : 1.0,
+        }
+    }
+
+    /// Loads the sound groups, a file that can not be read is left out of its group,
+    /// and synthesizes the tunes.
+    pub fn load(&mut self, ctx : &mut Context){
+        for name in synth::TUNES {
+            if let Some(tune) = synth::tune(name) {
+                self.tunes.insert(name.to_string(), synth::sound_data(&tune.render()));
+            }
+        }
+        for desc in GROUPS {
+            let mut variants = Vec::<audio::Source>::new();
+            for file in desc.files {
+                match audio::Source::new(ctx, file) {
+                    Ok(source) => variants.push(source),
+                    Err(e) => println!("Could not load sound {}: {}", file, e)
+                }
+            }
+            if variants.is_empty() {
+                continue;
+            }
+            self.groups.push(SoundGroup{ variants, pitch_jitter : desc.pitch_jitter });
+            self.group_names.insert(desc.name.to_string(), self.groups.len() - 1);
+        }
+    }
+
+    /// Makes a sound synthesized from `samples` the only variant of the group `name`,
+    /// the group is added if it is new.
+    pub fn set_synth_group(&mut self, name : &str, samples : &[f32], ctx : &mut Context){
+        let source = match audio::Source::from_data(ctx, synth::sound_data(samples)) {
+            Ok(source) => source,
+            Err(e) => {
+                println!("Could not play synthesized sound {}: {}", name, e);
+                return;
+            }
+        };
+        let group = SoundGroup{ variants : vec![source], pitch_jitter : SYNTH_PITCH_JITTER };
+        match self.group_names.get(name) {
+            Some(idx) => self.groups[*idx] = group,
+            None => {
+                self.groups.push(group);
+                self.group_names.insert(name.to_string(), self.groups.len() - 1);
+            }
+        }
+    }
+
+    /// The group to hand to `play`, if it was loaded.
+    pub fn group(&self, name : &str) -> Option<usize>{
+        self.group_names.get(name).copied()
+    }
+
+    /// Volumes of the effects and music buses, between 0 and 1.
+    pub fn set_gains(&mut self, sfx_gain : f32, music_gain : f32){
+        self.sfx_gain   = sfx_gain;
+        self.music_gain = music_gain;
+        self.apply_music_volume();
+    }
+
+    /// Plays a variant of the group at a jittered pitch. Each play gets a voice of its own,
+    /// so a sound played again does not cut the one still ringing.
+    pub fn play(&mut self, group_idx : usize){
+        let group = match self.groups.get_mut(group_idx){
+            Some(group) => group,
+            None => return
+        };
+        let mut rng = rand::thread_rng();
+        let jitter = group.pitch_jitter;
+        let source = group.variants.choose_mut(&mut rng).unwrap();
+        let pitch = if jitter > 0.0 { rng.gen_range(1.0 - jitter, 1.0 + jitter) } else { 1.0 };
+        source.set_pitch(pitch);
+        source.set_volume(self.sfx_gain);
+        if let Err(e) = source.play_detached() {
+            println!("Could not play sound: {}", e);
+        }
+    }
+
+    /// Cross-fades to the looping music `name`, a synthesized tune or a file of the resources.
+    /// An empty name fades the music out, the music already playing goes on.
+    pub fn play_music(&mut self, name : &str, ctx : &mut Context){
+        if self.music.as_ref().is_some_and(|t| t.name == name) {
+            return;
+        }
+        if let Some(track) = self.music.take() {
+            self.fading.push(track);
+        }
+        if name.is_empty() {
+            return;
+        }
+        // a track still fading out comes back from where it is.
+        if let Some(idx) = self.fading.iter().position(|t| t.name == name) {
+            self.music = Some(self.fading.remove(idx));
+            return;
+        }
+        let source = match self.tunes.get(name) {
+            Some(data) => audio::Source::from_data(ctx, data.clone()),
+            None => audio::Source::new(ctx, name)
+        };
+        let mut source = match source {
+            Ok(source) => source,
+            Err(e) => {
+                println!("Could not load music {}: {}", name, e);
+                return;
+            }
+        };
+        source.set_repeat(true);
+        source.set_volume(0.0);
+        if let Err(e) = source.play() {
+            println!("Could not play music {}: {}", name, e);
+            return;
+        }
+        self.music = Some(Track{ name : name.to_string(), source, fade : 0.0 });
+    }
+
+    /// Moves the cross-fade `dt` seconds on, the tracks faded out are stopped.
+    pub fn update(&mut self, dt : f32){
+        let step = dt / CROSSFADE_TIME;
+        if let Some(track) = self.music.as_mut() {
+            track.fade = (track.fade + step).min(1.0);
+        }
+        for track in &mut self.fading {
+            track.fade -= step;
+            if track.fade <= 0.0 {
+                track.source.stop();
+            }
+        }
+        self.fading.retain(|t| t.fade > 0.0);
+        self.apply_music_volume();
+    }
+
+    fn apply_music_volume(&mut self){
+        let gain = self.music_gain;
+        for track in self.music.iter_mut().chain(self.fading.iter_mut()) {
+            track.source.set_volume(track.fade * gain);
+        }
+    }
+}
@@ -0,0 +1,497 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use ggez::audio::SoundData;
+use rand::Rng;
+
+use crate::sound;
+use crate::unit::*;
+
+/// Samples per second of the synthesized sounds, mono.
+pub const SAMPLE_RATE : u32 = 44100;
+
+/// The shape of one period of the sound.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Waveform{
+    /// High for the `duty` fraction of the period, 0.5 is a plain square.
+    Square{ duty : f32 },
+    Sawtooth,
+    Sine,
+    /// A new random level each period, its pitch sets how rough it sounds.
+    Noise,
+}
+
+/// Volume over time, in seconds : it rises during `attack`, holds during `sustain`
+/// (`punch` louder at its start) and fades out during `decay`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Envelope{
+    pub attack  : f32,
+    pub sustain : f32,
+    pub punch   : f32,
+    pub decay   : f32,
+}
+
+impl Envelope{
+    pub fn duration(&self) -> f32{
+        self.attack + self.sustain + self.decay
+    }
+
+    /// Volume `t` seconds in, from 0 to 1 plus the punch.
+    pub fn at(&self, t : f32) -> f32{
+        if t < self.attack {
+            return t / self.attack;
+        }
+        let t = t - self.attack;
+        if t < self.sustain {
+            return 1.0 + self.punch * (1.0 - t / self.sustain);
+        }
+        let t = t - self.sustain;
+        if self.decay <= 0.0 { 0.0 } else { (1.0 - t / self.decay).max(0.0) }
+    }
+}
+
+/// An sfxr style sound effect.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SoundParams{
+    pub waveform      : Waveform,
+    pub envelope      : Envelope,
+    /// Pitch at the start, in Hz.
+    pub frequency     : f32,
+    /// The sound stops once a slide takes the pitch under this, in Hz.
+    pub min_frequency : f32,
+    /// Pitch change in octaves per second, and the change of that slide per second.
+    pub slide         : f32,
+    pub delta_slide   : f32,
+    /// The pitch wobbles by `vibrato_depth` of itself, `vibrato_speed` times per second.
+    pub vibrato_depth : f32,
+    pub vibrato_speed : f32,
+    /// The pitch jumps `arpeggio` times higher `arpeggio_time` seconds in, 1 keeps it.
+    pub arpeggio      : f32,
+    pub arpeggio_time : f32,
+    /// White noise mixed over the waveform, from 0 to 1.
+    pub noise         : f32,
+    pub volume        : f32,
+}
+
+impl Default for SoundParams{
+    fn default() -> Self{
+        SoundParams{
+            waveform      : Waveform::Square{ duty : 0.5 },
+            envelope      : Envelope{ attack : 0.0, sustain : 0.1, punch : 0.0, decay : 0.2 },
+            frequency     : 440.0,
+            min_frequency : 20.0,
+            slide         : 0.0,
+            delta_slide   : 0.0,
+            vibrato_depth : 0.0,
+            vibrato_speed : 0.0,
+            arpeggio      : 1.0,
+            arpeggio_time : 0.0,
+            noise         : 0.0,
+            volume        : 0.5,
+        }
+    }
+}
+
+impl SoundParams{
+    /// A short bright blip jumping up, for the pickups.
+    pub fn pickup(rng : &mut GameRng) -> Self{
+        SoundParams{
+            waveform      : if rng.gen::<bool>() { Waveform::Square{ duty : rng.gen_range(0.2, 0.5) } } else { Waveform::Sawtooth },
+            envelope      : Envelope{ attack : 0.0, sustain : rng.gen_range(0.02, 0.1), punch : rng.gen_range(0.3, 0.6), decay : rng.gen_range(0.1, 0.3) },
+            frequency     : rng.gen_range(600.0, 1400.0),
+            arpeggio      : rng.gen_range(1.2, 1.8),
+            arpeggio_time : rng.gen_range(0.03, 0.08),
+            ..SoundParams::default()
+        }
+    }
+
+    /// A low rumble of noise sliding down, for the crashes.
+    pub fn explosion(rng : &mut GameRng) -> Self{
+        SoundParams{
+            waveform      : Waveform::Noise,
+            envelope      : Envelope{ attack : 0.0, sustain : rng.gen_range(0.05, 0.3), punch : rng.gen_range(0.2, 0.8), decay : rng.gen_range(0.3, 0.7) },
+            frequency     : rng.gen_range(300.0, 1200.0),
+            slide         : rng.gen_range(-2.0, -0.5),
+            vibrato_depth : if rng.gen::<bool>() { rng.gen_range(0.05, 0.3) } else { 0.0 },
+            vibrato_speed : rng.gen_range(5.0, 20.0),
+            ..SoundParams::default()
+        }
+    }
+
+    /// A quick zap falling in pitch, for the shots.
+    pub fn laser(rng : &mut GameRng) -> Self{
+        let waveform = match rng.gen_range(0, 3) {
+            0 => Waveform::Square{ duty : rng.gen_range(0.1, 0.5) },
+            1 => Waveform::Sawtooth,
+            _ => Waveform::Sine,
+        };
+        SoundParams{
+            waveform,
+            envelope      : Envelope{ attack : 0.0, sustain : rng.gen_range(0.02, 0.08), punch : rng.gen_range(0.0, 0.4), decay : rng.gen_range(0.05, 0.2) },
+            frequency     : rng.gen_range(700.0, 1600.0),
+            min_frequency : rng.gen_range(80.0, 200.0),
+            slide         : rng.gen_range(-8.0, -3.0),
+            delta_slide   : rng.gen_range(0.0, 10.0),
+            noise         : rng.gen_range(0.0, 0.1),
+            volume        : 0.3,
+            ..SoundParams::default()
+        }
+    }
+
+    /// The sound as samples between -1 and 1, at `SAMPLE_RATE`.
+    /// The noise comes from `seed`, so the same params and seed give the same samples.
+    pub fn render(&self, seed : u64) -> Vec::<f32>{
+        let mut rng = seeded_rng(seed);
+        let dt = 1.0 / SAMPLE_RATE as f32;
+        let count = (self.envelope.duration() * SAMPLE_RATE as f32) as usize;
+        let mut samples = Vec::<f32>::with_capacity(count);
+
+        let mut frequency = self.frequency;
+        let mut slide = self.slide;
+        let mut phase = 0.0f32;
+        let mut noise_level = rng.gen_range(-1.0, 1.0);
+        for i in 0..count {
+            let t = i as f32 * dt;
+            slide += self.delta_slide * dt;
+            frequency *= (slide * dt).exp2();
+            if frequency < self.min_frequency {
+                break;
+            }
+            let mut pitch = frequency;
+            if self.arpeggio_time > 0.0 && t >= self.arpeggio_time {
+                pitch *= self.arpeggio;
+            }
+            pitch *= 1.0 + self.vibrato_depth * (2.0 * PI * self.vibrato_speed * t).sin();
+
+            phase += pitch * dt;
+            if phase >= 1.0 {
+                phase = phase.fract();
+                noise_level = rng.gen_range(-1.0, 1.0);
+            }
+            let wave = match self.waveform {
+                Waveform::Square{ duty } => if phase < duty { 1.0 } else { -1.0 },
+                Waveform::Sawtooth => 1.0 - 2.0 * phase,
+                Waveform::Sine     => (2.0 * PI * phase).sin(),
+                Waveform::Noise    => noise_level,
+            };
+            let noisy = wave * (1.0 - self.noise) + rng.gen_range(-1.0, 1.0) * self.noise;
+            let sample = noisy * self.envelope.at(t) * self.volume;
+            samples.push(sample.clamp(-1.0, 1.0));
+        }
+        samples
+    }
+}
+
+/// The samples as a 16 bit mono WAV file.
+pub fn wav_bytes(samples : &[f32]) -> Vec::<u8>{
+    const HEADER_SIZE : usize = 44;
+    let data_size = (samples.len() * 2) as u32;
+    let mut bytes = Vec::<u8>::with_capacity(HEADER_SIZE + data_size as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel, 2 bytes per sample.
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for s in samples {
+        let pcm = (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&pcm.to_le_bytes());
+    }
+    bytes
+}
+
+pub fn write_wav(path : &Path, samples : &[f32]) -> io::Result<()>{
+    if let Some(dir) = path.parent(){
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, wav_bytes(samples))
+}
+
+/// The samples as a sound ggez can play.
+pub fn sound_data(samples : &[f32]) -> SoundData{
+    SoundData::from_bytes(&wav_bytes(samples))
+}
+
+/// The pickup, death and fire sounds of a level, all derived from one seed.
+pub struct LevelSounds{
+    pub pickup : SoundParams,
+    pub death  : SoundParams,
+    pub fire   : SoundParams,
+    seed       : u64,
+}
+
+impl LevelSounds{
+    pub fn new(seed : u64) -> Self{
+        let mut rng = seeded_rng(seed);
+        LevelSounds{
+            pickup : SoundParams::pickup(&mut rng),
+            death  : SoundParams::explosion(&mut rng),
+            fire   : SoundParams::laser(&mut rng),
+            seed,
+        }
+    }
+
+    /// Each sound rendered, with the mixer group it plays in.
+    pub fn render(&self) -> Vec::<(&'static str, Vec::<f32>)>{
+        vec![
+            (sound::PICKUP_SOUND, self.pickup.render(self.seed)),
+            (sound::DEATH_SOUND, self.death.render(self.seed)),
+            (sound::FIRE_SOUND, self.fire.render(self.seed)),
+        ]
+    }
+
+    /// Writes each sound as `<group>.wav` in `dir`.
+    pub fn export(&self, dir : &Path) -> io::Result<()>{
+        for (name, samples) in self.render() {
+            write_wav(&dir.join(format!("{}.wav", name)), &samples)?;
+        }
+        Ok(())
+    }
+}
+
+/// A voice of a tune : its instrument plays `notes`, one step each, in semitones
+/// from the frequency of the instrument. `None` rests, and notes shorter than the tune repeat.
+pub struct Voice{
+    pub instrument : SoundParams,
+    pub notes      : &'static [Option<i32>],
+}
+
+/// A looping tune, its voices playing along one `step` seconds long note at a time.
+pub struct Tune{
+    pub step   : f32,
+    pub voices : Vec::<Voice>,
+}
+
+impl Tune{
+    /// The whole loop as samples. The notes ringing past its end come back at its start,
+    /// so the samples loop without a gap.
+    pub fn render(&self) -> Vec::<f32>{
+        let nb_steps = self.voices.iter().map(|v| v.notes.len()).max().unwrap_or(0);
+        let step_samples = (self.step * SAMPLE_RATE as f32) as usize;
+        let mut samples = vec![0.0f32; nb_steps * step_samples];
+        if samples.is_empty() {
+            return samples;
+        }
+        let len = samples.len();
+        for voice in self.voices.iter().filter(|v| !v.notes.is_empty()) {
+            for i in 0..nb_steps {
+                let semitones = match voice.notes[i % voice.notes.len()] {
+                    Some(semitones) => semitones,
+                    None => continue
+                };
+                let params = SoundParams{
+                    frequency : voice.instrument.frequency * (semitones as f32 / 12.0).exp2(),
+                    ..voice.instrument
+                };
+                for (j, s) in params.render(i as u64).iter().enumerate() {
+                    samples[(i * step_samples + j) % len] += s;
+                }
+            }
+        }
+        for s in &mut samples {
+            *s = s.clamp(-1.0, 1.0);
+        }
+        samples
+    }
+}
+
+/// Names of the tunes the mixer plays as music.
+pub const MENU_TUNE   : &str = "menu";
+pub const CAVERN_TUNE : &str = "cavern";
+pub const TUNES : [&str; 2] = [MENU_TUNE, CAVERN_TUNE];
+
+/// A soft square pad, for the menus.
+fn pad(frequency : f32) -> SoundParams{
+    SoundParams{
+        waveform : Waveform::Square{ duty : 0.25 },
+        envelope : Envelope{ attack : 0.02, sustain : 0.2, punch : 0.0, decay : 0.3 },
+        frequency,
+        volume   : 0.12,
+        ..SoundParams::default()
+    }
+}
+
+/// A plucked sawtooth, for the bass lines.
+fn bass(frequency : f32) -> SoundParams{
+    SoundParams{
+        waveform : Waveform::Sawtooth,
+        envelope : Envelope{ attack : 0.0, sustain : 0.08, punch : 0.3, decay : 0.1 },
+        frequency,
+        volume   : 0.2,
+        ..SoundParams::default()
+    }
+}
+
+/// A short noise burst, for the beat.
+fn drum() -> SoundParams{
+    SoundParams{
+        waveform : Waveform::Noise,
+        envelope : Envelope{ attack : 0.0, sustain : 0.01, punch : 0.5, decay : 0.08 },
+        frequency : 2000.0,
+        slide     : -3.0,
+        volume    : 0.1,
+        ..SoundParams::default()
+    }
+}
+
+const MENU_MELODY : [Option<i32>; 32] = [
+    Some(0), None, Some(3), None, Some(7), None, Some(10), None,
+    Some(8), None, Some(7), None, Some(3), None, None, None,
+    Some(-2), None, Some(2), None, Some(5), None, Some(9), None,
+    Some(7), None, Some(5), None, Some(2), None, None, None,
+];
+const MENU_BASS : [Option<i32>; 32] = [
+    Some(0), None, None, None, None, None, None, None,
+    Some(-4), None, None, None, None, None, None, None,
+    Some(-2), None, None, None, None, None, None, None,
+    Some(-5), None, None, None, None, None, None, None,
+];
+const CAVERN_LEAD : [Option<i32>; 32] = [
+    Some(0), None, Some(0), Some(3), None, Some(5), None, Some(3),
+    Some(7), None, Some(5), None, Some(3), None, Some(2), None,
+    Some(0), None, Some(0), Some(3), None, Some(5), None, Some(7),
+    Some(10), None, Some(8), None, Some(7), None, Some(5), None,
+];
+const CAVERN_BASS : [Option<i32>; 32] = [
+    Some(0), Some(0), Some(12), Some(0), Some(0), Some(12), Some(0), Some(12),
+    Some(-4), Some(-4), Some(8), Some(-4), Some(-4), Some(8), Some(-4), Some(8),
+    Some(-2), Some(-2), Some(10), Some(-2), Some(-2), Some(10), Some(-2), Some(10),
+    Some(-5), Some(-5), Some(7), Some(-5), Some(-2), Some(10), Some(-2), Some(10),
+];
+const CAVERN_BEAT : [Option<i32>; 8] = [Some(0), None, Some(0), None, Some(0), None, Some(0), Some(0)];
+
+/// The tune playing as `name`, if the game has one.
+pub fn tune(name : &str) -> Option<Tune>{
+    match name {
+        MENU_TUNE => Some(Tune{
+            step   : 0.25,
+            voices : vec![
+                Voice{ instrument : pad(440.0), notes : &MENU_MELODY },
+                Voice{ instrument : pad(220.0), notes : &MENU_BASS },
+            ],
+        }),
+        CAVERN_TUNE => Some(Tune{
+            step   : 0.14,
+            voices : vec![
+                Voice{ instrument : SoundParams{ volume : 0.1, ..pad(587.0) }, notes : &CAVERN_LEAD },
+                Voice{ instrument : bass(73.4), notes : &CAVERN_BASS },
+                Voice{ instrument : drum(), notes : &CAVERN_BEAT },
+            ],
+        }),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn sample_count(params : &SoundParams) -> usize{
+        (params.envelope.duration() * SAMPLE_RATE as f32) as usize
+    }
+
+    #[test]
+    fn render_is_deterministic(){
+        let params = SoundParams::explosion(&mut seeded_rng(7));
+        assert_eq!(params.render(3), params.render(3));
+        assert_ne!(params.render(3), params.render(4));
+    }
+
+    #[test]
+    fn render_lasts_the_envelope(){
+        let params = SoundParams::default();
+        assert_eq!(params.render(0).len(), sample_count(&params));
+    }
+
+    #[test]
+    fn render_stops_under_min_frequency(){
+        let params = SoundParams{
+            frequency     : 400.0,
+            min_frequency : 200.0,
+            slide         : -4.0,
+            ..SoundParams::default()
+        };
+        // one octave down at 4 octaves per second, give or take a millisecond of rounding.
+        let expected = (0.25 * SAMPLE_RATE as f32) as i64;
+        let len = params.render(0).len();
+        assert!(len < sample_count(&params));
+        assert!((len as i64 - expected).abs() <= (SAMPLE_RATE / 1000) as i64, "{} samples, {} expected", len, expected);
+    }
+
+    #[test]
+    fn render_stays_in_range(){
+        let loud = SoundParams{
+            envelope : Envelope{ attack : 0.01, sustain : 0.1, punch : 2.0, decay : 0.1 },
+            noise    : 0.5,
+            volume   : 3.0,
+            ..SoundParams::default()
+        };
+        let mut rng = seeded_rng(11);
+        let mut all = vec![loud];
+        for _ in 0..10 {
+            all.push(SoundParams::pickup(&mut rng));
+            all.push(SoundParams::explosion(&mut rng));
+            all.push(SoundParams::laser(&mut rng));
+        }
+        for params in all {
+            assert!(params.render(5).iter().all(|s| (-1.0..=1.0).contains(s)), "{:?}", params);
+        }
+    }
+
+    #[test]
+    fn wav_header(){
+        let samples = vec![0.0, 1.0, -1.0, 0.5];
+        let bytes = wav_bytes(&samples);
+        let u32_at = |i : usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let u16_at = |i : usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+
+        assert_eq!(bytes.len(), 44 + 2 * samples.len());
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(16), 16);
+        assert_eq!(u16_at(20), 1);
+        assert_eq!(u16_at(22), 1);
+        assert_eq!(u32_at(24), SAMPLE_RATE);
+        assert_eq!(u32_at(28), SAMPLE_RATE * 2);
+        assert_eq!(u16_at(32), 2);
+        assert_eq!(u16_at(34), 16);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(40) as usize, 2 * samples.len());
+        assert_eq!(u16_at(46) as i16, i16::MAX);
+        assert_eq!(u16_at(48) as i16, -i16::MAX);
+    }
+
+    #[test]
+    fn tunes_loop_whole_steps(){
+        for name in &TUNES {
+            let tune = tune(name).unwrap();
+            let nb_steps = tune.voices.iter().map(|v| v.notes.len()).max().unwrap();
+            let samples = tune.render();
+            assert_eq!(samples.len(), nb_steps * (tune.step * SAMPLE_RATE as f32) as usize);
+            assert!(samples.iter().all(|s| (-1.0..=1.0).contains(s)));
+            assert!(samples.iter().any(|s| *s != 0.0));
+        }
+    }
+
+    #[test]
+    fn level_sounds_follow_the_seed(){
+        let a = LevelSounds::new(42);
+        let b = LevelSounds::new(42);
+        assert_eq!((a.pickup, a.death, a.fire), (b.pickup, b.death, b.fire));
+        assert_eq!(a.render(), b.render());
+
+        let c = LevelSounds::new(43);
+        assert_ne!((a.pickup, a.death, a.fire), (c.pickup, c.death, c.fire));
+    }
+}
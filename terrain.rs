@@ -1,5 +1,4 @@
 use rand::Rng;
-use rand::rngs::ThreadRng;
 use rand::seq::SliceRandom;
 // use rand::seq::IteratorRandom;
 use std::ops::RangeInclusive;
@@ -24,13 +23,11 @@ const UP      : Position         = Position{x:0.0, y:1.0};
 const FORWARD : Position         = Position{x:1.0, y:0.0};
 
 
-fn random_direction(last_direction : &SlopeDirection) -> &SlopeDirection{    
-    let mut rng = rand::thread_rng();    
-    DIRS.choose(&mut rng).unwrap()
+fn random_direction(last_direction : &SlopeDirection, rng : &mut GameRng) -> &'static SlopeDirection{    
+    DIRS.choose(rng).unwrap()
 }
 
-fn random_length( max : f32) -> f32{
-    let mut rng = rand::thread_rng();    
+fn random_length( max : f32, rng : &mut GameRng) -> f32{
     let low : f32 = 10.0;
     if max < low{
         return max;
@@ -48,8 +45,8 @@ pub fn build_sky(bounds : &Bounds2D) -> Vec::<Position>{
 }
 
 
-pub fn build_terrain(bounds : &Bounds2D, max_length : f32)-> Vec::<Position>{
-    let mut points = _build_terrain(bounds.get_size(), max_length);
+pub fn build_terrain(bounds : &Bounds2D, max_length : f32, rng : &mut GameRng)-> Vec::<Position>{
+    let mut points = _build_terrain(bounds.get_size(), max_length, rng);
     for p in &mut points{
         p.y += bounds.min.y;
         p.x += bounds.min.x;
@@ -70,7 +67,7 @@ pub fn invert_pos( world_size : &Size, points : &mut Vec::<Position>, insert: bo
         
 }
 
-fn _build_terrain(world_size : Size, max_length : f32) -> Vec::<Position>{    
+fn _build_terrain(world_size : Size, max_length : f32, rng : &mut GameRng) -> Vec::<Position>{    
     let tan45 : f32 = (45.0 as f32).to_radians().tan();
     let mut length = 0.0;
     let mut last_direction = &SlopeDirection::Flat;
@@ -78,8 +75,8 @@ fn _build_terrain(world_size : Size, max_length : f32) -> Vec::<Position>{
     points.push( Position{ x:0.0, y:0.0}  );
 
     while length < world_size.x{
-        let direction     = random_direction(last_direction);
-        let segment_lenth = random_length(( world_size.x - length).min(max_length));
+        let direction     = random_direction(last_direction, rng);
+        let segment_lenth = random_length(( world_size.x - length).min(max_length), rng);
         let last_point    = points.last().unwrap();
         let x = last_point.x + segment_lenth*FORWARD.x;
         let mut y = last_point.y;
@@ -165,7 +162,7 @@ impl HeightRange{
         self.top <= self.bottom
     }
 
-    fn place_within(&self, height: f32, rng: &mut ThreadRng) -> Option<f32>{
+    fn place_within(&self, height: f32, rng: &mut GameRng) -> Option<f32>{
         if self.size() < height {
             return  None;
         }
@@ -177,7 +174,7 @@ impl HeightRange{
 }
 
 
-fn get_tunnel_height(world_range : HeightRange, segment_range: HeightRange, min_height: f32, max_vert_move: f32, rng :&mut ThreadRng) -> HeightRange{
+fn get_tunnel_height(world_range : HeightRange, segment_range: HeightRange, min_height: f32, max_vert_move: f32, rng :&mut GameRng) -> HeightRange{
     let (space_above, space_below) = world_range.diff(&segment_range);
     // size change
     let mut size_change = SizeChange::Same;
@@ -246,7 +243,7 @@ fn get_tunnel_height(world_range : HeightRange, segment_range: HeightRange, min_
 }
 
 
-fn get_section_length(posx :f32, maxx: f32, length_bounds : &Bounds1D<f32>, rng :&mut ThreadRng) -> f32{    
+fn get_section_length(posx :f32, maxx: f32, length_bounds : &Bounds1D<f32>, rng :&mut GameRng) -> f32{    
     let available_space    =  maxx - posx;
     if available_space < length_bounds.min{
         available_space
@@ -255,13 +252,12 @@ fn get_section_length(posx :f32, maxx: f32, length_bounds : &Bounds1D<f32>, rng
     }            
 }
 
-pub fn build_tunnel2(world_size : &Size, length_bounds : &Bounds1D<f32>, height_bounds: &Bounds1D<f32>, first_length: f32) -> (Vec::<HeightRange>, Vec::<f32>){    
+pub fn build_tunnel2(world_size : &Size, length_bounds : &Bounds1D<f32>, height_bounds: &Bounds1D<f32>, first_length: f32, rng : &mut GameRng) -> (Vec::<HeightRange>, Vec::<f32>){    
         
     
     let mut height_ranges = Vec::<HeightRange>::new();
     let mut xpositions = Vec::<f32>::new();
     let mut pos_x = 0.0;
-    let mut rng = rand::thread_rng();  
 
     let world_top    = world_size.y;
     let world_bottom = 0.0f32;
@@ -283,12 +279,12 @@ pub fn build_tunnel2(world_size : &Size, length_bounds : &Bounds1D<f32>, height_
     let mut current_range = start_segment;    
     while pos_x < world_size.x{
         
-        let segment_length = get_section_length(pos_x, world_size.x, &length_bounds, &mut rng );
+        let segment_length = get_section_length(pos_x, world_size.x, &length_bounds, rng );
         if segment_length == 0.0f32{
             break;
         }
         pos_x += segment_length;                   
-        current_range = get_tunnel_height2(&world_range, &current_range, height_bounds, segment_length, &mut rng);
+        current_range = get_tunnel_height2(&world_range, &current_range, height_bounds, segment_length, rng);
         height_ranges.push(current_range);
         xpositions.push(pos_x);
          
@@ -322,6 +318,24 @@ pub fn convert_to_polygons(height_ranges: &Vec::<HeightRange>, xpositions: &Vec<
     (top_pts, bot_pts)
 }
 
+/// Indices of the cells holding a checkpoint, the first one at least `spacing` after
+/// the start, then one every `spacing` or so. The first and last cells are left out,
+/// the level starts and ends there.
+pub fn checkpoint_cells(cells : &[Cell], spacing : f32) -> Vec::<usize> {
+    let mut result = Vec::<usize>::new();
+    let mut last_x = match cells.first() {
+        Some(c) => c.x00.x,
+        None    => return result
+    };
+    for (i, c) in cells.iter().enumerate().take(cells.len() - 1).skip(1) {
+        if c.x00.x - last_x >= spacing {
+            result.push(i);
+            last_x = c.x00.x;
+        }
+    }
+    result
+}
+
 pub fn convert_to_cells(height_ranges: &Vec::<HeightRange>, xpositions: &Vec<f32>) -> Vec::<Cell> {
     let mut result = Vec::<Cell>::new();
     let height_ranges_pairs = height_ranges.windows(2);
@@ -357,7 +371,7 @@ impl VectorRange{
         }
     }
 
-    pub fn gen_random_dir(&self, rng: &mut ThreadRng) -> Vector2 {
+    pub fn gen_random_dir(&self, rng: &mut GameRng) -> Vector2 {
         let t = rng.gen_range(0.0f32, 1.0f32);
         self.upward * t + self.downward * (1.0 -t)
     }
@@ -403,7 +417,7 @@ fn get_tangent_vector(o : Point2, p : Point2, radius : f32, up: bool) -> Vector2
     p2_p1
 } 
 
-fn get_tunnel_height2(world_range : &HeightRange, segment_range: &HeightRange, height_bounds: &Bounds1D<f32>, distance: f32, rng :&mut ThreadRng) -> HeightRange{
+fn get_tunnel_height2(world_range : &HeightRange, segment_range: &HeightRange, height_bounds: &Bounds1D<f32>, distance: f32, rng :&mut GameRng) -> HeightRange{
     
     let radius = height_bounds.min;
 
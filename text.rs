@@ -46,4 +46,19 @@ pub fn ui_style() -> FontStyle{
     }
 }
 
+pub fn menu_style() -> FontStyle{
+    FontStyle{
+        size: 34.0,
+        name: "V5PRD___".to_string(),
+        weight: FontWeight::Normal,
+        color: ggez::graphics::WHITE,
+    }
+}
 
+/// The menu item selected.
+pub fn menu_selected_style() -> FontStyle{
+    FontStyle{
+        color: crate::color::SKYBLUE,
+        ..menu_style()
+    }
+}
@@ -1,5 +1,4 @@
 use rand::Rng;
-use rand::rngs::ThreadRng;
 use rand::seq::SliceRandom;
 
 use crate::unit::*;
@@ -15,11 +14,11 @@ enum SizeChange{
 const SIZES   : [SizeChange;3]   = [SizeChange::Same, SizeChange::Bigger, SizeChange::Smaller];
 
 impl SizeChange{
-    fn get(rng :&mut ThreadRng) -> SizeChange {
+    fn get(rng :&mut GameRng) -> SizeChange {
         *SIZES.choose(rng).unwrap()
     }
 
-    fn get_slope(&self, rng :&mut ThreadRng) -> SlopeDirection{
+    fn get_slope(&self, rng :&mut GameRng) -> SlopeDirection{
         match self {
             SizeChange::Same => {
                 let possibilities = [SlopeDirection::Down, SlopeDirection::Up, SlopeDirection::Flat];
@@ -41,7 +40,7 @@ impl SizeChange{
 
 
 
-fn build_tunnel_section(world_top : f32, world_bottom : f32, max_length : f32, top_pt: &Position, bottom_pt: &Position, rng :&mut ThreadRng) -> (Position, Position) {
+fn build_tunnel_section(world_top : f32, world_bottom : f32, max_length : f32, top_pt: &Position, bottom_pt: &Position, rng :&mut GameRng) -> (Position, Position) {
     let tan45 : f32 = (45.0 as f32).to_radians().tan();
     let sizechange = SizeChange::get(rng);
     let direction= sizechange.get_slope(rng);
@@ -116,7 +115,7 @@ fn build_tunnel_section(world_top : f32, world_bottom : f32, max_length : f32, t
     return (top_vec, bot_vec);
 }
 
-pub fn build_tunnel(world_size : &Size, max_length : f32) -> (Vec::<Position>, Vec::<Position>){    
+pub fn build_tunnel(world_size : &Size, max_length : f32, rng : &mut GameRng) -> (Vec::<Position>, Vec::<Position>){    
     
     let mut length = 0.0;
 
@@ -127,11 +126,9 @@ pub fn build_tunnel(world_size : &Size, max_length : f32) -> (Vec::<Position>, V
     top_pts.push( Position{ x:0.0, y:tunnel_bottom+tunnel_height}  );
     bottom_pts.push( Position{ x:0.0, y:tunnel_bottom}  );
 
-    let mut rng = rand::thread_rng();  
-
     let world_top = world_size.y;
     let world_bottom = 0.0f32;
-    // top_pt: &Position, bottom_pt: &Position, rng :&mut ThreadRng) -> (Position, Position) {
+    // top_pt: &Position, bottom_pt: &Position, rng :&mut GameRng) -> (Position, Position) {
 
     while length < world_size.x{
         let segment_lenth = rng.gen_range(0.0f32, ( world_size.x - length).min(max_length));
@@ -141,7 +138,7 @@ pub fn build_tunnel(world_size : &Size, max_length : f32) -> (Vec::<Position>, V
         length += segment_lenth;
         let top_pt = top_pts.last().unwrap();
         let bottom_pt = bottom_pts.last().unwrap();
-        let (top_pt, bot_pt) = build_tunnel_section(world_top, world_bottom, segment_lenth, top_pt, bottom_pt, &mut rng);
+        let (top_pt, bot_pt) = build_tunnel_section(world_top, world_bottom, segment_lenth, top_pt, bottom_pt, rng);
         top_pts.push(top_pt);
         bottom_pts.push(bot_pt);
     }
@@ -2,13 +2,42 @@ use std::collections::HashMap;
 use std::ops::Sub;
 use std::marker::Copy;
 pub use nalgebra as na;
+use rand::SeedableRng;
 
 pub type Position = mint::Point2::<f32>;
 pub type Size     = mint::Point2::<f32>;
 pub type Id       = uuid::Uuid;
+pub type GameRng  = rand::rngs::StdRng;
 
 pub static Origin : Position = Position{x:0.0, y:0.0};
 
+/// Placement of an actor : where it is, its rotation in radians and its uniform scale.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform{
+    pub position : Position,
+    pub rotation : f32,
+    pub scale    : f32,
+}
+
+impl Transform{
+    pub fn from_position(position : Position) -> Transform{
+        Transform{
+            position,
+            rotation : 0.0,
+            scale    : 1.0,
+        }
+    }
+
+    pub fn identity() -> Transform{
+        Transform::from_position(Origin)
+    }
+
+    /// Position and rotation, the scale is applied to the shapes themselves.
+    pub fn isometry(&self) -> na::Isometry2::<f32>{
+        na::Isometry2::new(Vector2::new(self.position.x, self.position.y), self.rotation)
+    }
+}
+
 pub type Vector2 = na::Vector2::<f32>;
 pub type Point2  = na::Point2::<f32>;
 
@@ -24,6 +53,12 @@ pub fn get_id() -> uuid::Uuid {
     uuid::Uuid::new_v4()
 }
 
+/// All level generation draws from a `GameRng` built from a seed,
+/// so the same seed always rebuilds the same world.
+pub fn seeded_rng(seed : u64) -> GameRng {
+    GameRng::seed_from_u64(seed)
+}
+
 pub fn  no_id() -> uuid::Uuid{
     uuid::Uuid::nil()
 }